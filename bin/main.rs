@@ -1,6 +1,24 @@
+mod progress;
+
 use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
+use mobula::adaptive::Adaptive;
+use mobula::aov::Aov;
+use mobula::cancel::CancelToken;
+use mobula::checkpoint::Checkpoint;
+use mobula::config::Config;
+use mobula::filter::Filter;
+use mobula::framebuffer::Framebuffer;
+use mobula::integrator::Integrator;
+use mobula::progress::{Progress, Silent};
+use mobula::sampler::Sampler;
 use mobula::scene::Scene;
+use mobula::tile::{Tile, TileOrder};
+use mobula::tone_map::{ToneMap, Transfer};
+
+use progress::Style;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let matches = clap::App::new("mobula")
@@ -31,7 +49,7 @@ objects, camera, and rendering settings.",
                 .short('h')
                 .takes_value(true),
             clap::Arg::with_name("depth")
-                .help("Maximum depth of reflections")
+                .help("the most bounces a path can take")
                 .long("depth")
                 .short('d')
                 .takes_value(true),
@@ -40,16 +58,193 @@ objects, camera, and rendering settings.",
                 .long("samples")
                 .short('s')
                 .takes_value(true),
+            clap::Arg::with_name("progressive")
+                .help("render in passes of this many samples, writing the image after each")
+                .long("progressive")
+                .value_name("SAMPLES")
+                .takes_value(true),
+            clap::Arg::with_name("tile-size")
+                .help("the width and height of the tiles the image is rendered in")
+                .long("tile-size")
+                .value_name("PIXELS")
+                .takes_value(true),
+            clap::Arg::with_name("tile-order")
+                .help("the order tiles are rendered in")
+                .long("tile-order")
+                .possible_values(TileOrder::NAMES)
+                .takes_value(true),
+            clap::Arg::with_name("time-limit")
+                .help("stop the render after this long, and write the image so far")
+                .long("time-limit")
+                .value_name("SECONDS")
+                .takes_value(true),
+            clap::Arg::with_name("region")
+                .help("only render these pixels into the --checkpoint, to be merged with renders of the rest")
+                .long("region")
+                .value_names(&["X", "Y", "W", "H"])
+                .number_of_values(4)
+                .use_value_delimiter(true)
+                .conflicts_with("resume")
+                .requires("checkpoint")
+                .takes_value(true),
+            clap::Arg::with_name("sample-range")
+                .help("only take samples START up to END of the --samples of each pixel into the --checkpoint, to be merged with renders of the rest")
+                .long("sample-range")
+                .value_names(&["START", "END"])
+                .number_of_values(2)
+                .use_value_delimiter(true)
+                .conflicts_with("resume")
+                .requires("checkpoint")
+                .takes_value(true),
+            clap::Arg::with_name("checkpoint")
+                .help("save the render to this file as it goes, so it can be resumed or merged")
+                .long("checkpoint")
+                .alias("partial")
+                .value_name("FILE")
+                .takes_value(true),
+            clap::Arg::with_name("checkpoint-interval")
+                .help("how often to save the checkpoint")
+                .long("checkpoint-interval")
+                .value_name("SECONDS")
+                .default_value("60")
+                .takes_value(true),
+            clap::Arg::with_name("resume")
+                .help("carry on a render from a checkpoint, up to --samples in total or the end of its --sample-range")
+                .long("resume")
+                .value_name("FILE")
+                .takes_value(true),
+            clap::Arg::with_name("max-samples")
+                .help("keep sampling noisy pixels, up to this many samples")
+                .long("max-samples")
+                .takes_value(true),
+            clap::Arg::with_name("error")
+                .help("with adaptive sampling, the relative error to stop sampling a pixel at")
+                .long("error")
+                .takes_value(true),
+            clap::Arg::with_name("filter")
+                .help("the filter used to turn samples into pixels")
+                .long("filter")
+                .possible_values(Filter::NAMES)
+                .takes_value(true),
+            clap::Arg::with_name("filter-radius")
+                .help("the radius of the filter, in pixels")
+                .long("filter-radius")
+                .value_name("PIXELS")
+                .takes_value(true),
+            clap::Arg::with_name("sampler")
+                .help("how samples are spread out")
+                .long("sampler")
+                .possible_values(Sampler::NAMES)
+                .takes_value(true),
+            clap::Arg::with_name("seed")
+                .help("the seed for the random numbers used to render")
+                .long("seed")
+                .takes_value(true),
+            clap::Arg::with_name("integrator")
+                .help("how the light reaching the camera is worked out")
+                .long("integrator")
+                .possible_values(Integrator::NAMES)
+                .takes_value(true),
+            clap::Arg::with_name("no-light-sampling")
+                .help("only find lights by bouncing rays around, without shadow rays")
+                .long("no-light-sampling"),
+            clap::Arg::with_name("exposure")
+                .help("brighten or darken the image by this many stops")
+                .long("exposure")
+                .short('e')
+                .value_name("EV")
+                .allow_hyphen_values(true)
+                .takes_value(true),
+            clap::Arg::with_name("tone-map")
+                .help("how to fit bright colours into the image")
+                .long("tone-map")
+                .possible_values(ToneMap::NAMES)
+                .takes_value(true),
+            clap::Arg::with_name("transfer")
+                .help("the transfer function used to encode the image")
+                .long("transfer")
+                .possible_values(Transfer::NAMES)
+                .takes_value(true),
+            clap::Arg::with_name("aov")
+                .help("also render these passes")
+                .long_help(
+                    "Render extra passes alongside the image. They're
+added to .exr files as extra channels, and written
+next to other formats with the pass's name added,
+like a.depth.png.",
+                )
+                .long("aov")
+                .value_name("AOV")
+                .possible_values(Aov::NAMES)
+                .multiple_occurrences(true)
+                .use_value_delimiter(true)
+                .takes_value(true),
+            clap::Arg::with_name("progress")
+                .help("how to show progress: a bar, or a line of JSON per event on stderr")
+                .long("progress")
+                .possible_values(Style::NAMES)
+                .default_value("bar")
+                .takes_value(true),
+            clap::Arg::with_name("quiet")
+                .help("don't show progress or say what's being written")
+                .long("quiet")
+                .short('q')
+                .conflicts_with("progress"),
             clap::Arg::with_name("out")
                 .help("write output to FILE")
+                .long_help(
+                    "Where to write the image. Files ending in .exr, .hdr
+or .pfm get linear floating-point colours, anything
+else is tone mapped into an 8-bit image.",
+                )
                 .long("out")
                 .short('o')
                 .value_name("FILE")
                 .default_value("a.png")
                 .takes_value(true),
         ])
+        .subcommand_negates_reqs(true)
+        .subcommand(
+            clap::App::new("merge")
+                .about("Put together renders of parts of an image from their checkpoints")
+                .args(&[
+                    clap::Arg::with_name("scene")
+                        .help("the scene file the parts were rendered from")
+                        .value_name("FILE")
+                        .index(1)
+                        .required(true),
+                    clap::Arg::with_name("checkpoints")
+                        .help("the checkpoints of the parts")
+                        .value_name("CHECKPOINT")
+                        .index(2)
+                        .multiple_values(true)
+                        .required(true),
+                    clap::Arg::with_name("aov")
+                        .help("render an extra pass alongside the image")
+                        .long("aov")
+                        .possible_values(Aov::NAMES)
+                        .multiple_occurrences(true)
+                        .use_value_delimiter(true)
+                        .takes_value(true),
+                    clap::Arg::with_name("out")
+                        .help("write output to FILE")
+                        .long("out")
+                        .short('o')
+                        .value_name("FILE")
+                        .default_value("a.png")
+                        .takes_value(true),
+                    clap::Arg::with_name("quiet")
+                        .help("don't say what's being written")
+                        .long("quiet")
+                        .short('q'),
+                ]),
+        )
         .get_matches();
 
+    if let Some(("merge", matches)) = matches.subcommand() {
+        return merge(matches);
+    }
+
     // `unwrap` is safe as it's a required arg.
     let file = File::open(matches.value_of("scene").unwrap())?;
     let mut scene: Scene = serde_json::from_reader(file)?;
@@ -67,12 +262,295 @@ objects, camera, and rendering settings.",
     if matches.is_present("samples") {
         scene.config.samples = clap::value_t!(matches, "samples", u32).unwrap_or_else(|e| e.exit());
     }
+    if matches.is_present("tile-size") {
+        scene.config.tile_size =
+            clap::value_t!(matches, "tile-size", u32).unwrap_or_else(|e| e.exit());
+    }
+    if matches.is_present("tile-order") {
+        scene.config.tile_order =
+            clap::value_t!(matches, "tile-order", TileOrder).unwrap_or_else(|e| e.exit());
+    }
+    if matches.is_present("progressive") {
+        scene.config.progressive =
+            Some(clap::value_t!(matches, "progressive", u32).unwrap_or_else(|e| e.exit()));
+    }
+    if matches.is_present("max-samples") {
+        let max_samples = clap::value_t!(matches, "max-samples", u32).unwrap_or_else(|e| e.exit());
+        scene.config.adaptive = Some(Adaptive {
+            max_samples,
+            ..scene.config.adaptive.unwrap_or_default()
+        });
+    }
+    if matches.is_present("error") {
+        let error = clap::value_t!(matches, "error", f64).unwrap_or_else(|e| e.exit());
+        scene.config.adaptive = Some(Adaptive {
+            error,
+            ..scene.config.adaptive.unwrap_or_default()
+        });
+    }
+    if matches.is_present("filter") {
+        scene.config.filter =
+            clap::value_t!(matches, "filter", Filter).unwrap_or_else(|e| e.exit());
+    }
+    if matches.is_present("filter-radius") {
+        let radius = clap::value_t!(matches, "filter-radius", f64).unwrap_or_else(|e| e.exit());
+        scene.config.filter = scene.config.filter.with_radius(radius);
+    }
+    if matches.is_present("sampler") {
+        scene.config.sampler =
+            clap::value_t!(matches, "sampler", Sampler).unwrap_or_else(|e| e.exit());
+    }
+    if matches.is_present("seed") {
+        scene.config.seed = clap::value_t!(matches, "seed", u64).unwrap_or_else(|e| e.exit());
+    }
+    if matches.is_present("integrator") {
+        let integrator =
+            clap::value_t!(matches, "integrator", Integrator).unwrap_or_else(|e| e.exit());
+        // Only the name can be given here, so keep any settings the scene
+        // already has for the same integrator.
+        scene.config.integrator = match (integrator, scene.config.integrator) {
+            (Integrator::AmbientOcclusion { .. }, kept @ Integrator::AmbientOcclusion { .. }) => {
+                kept
+            }
+            _ => integrator,
+        };
+    }
+    if matches.is_present("no-light-sampling") {
+        scene.config.light_sampling = false;
+    }
+    if matches.is_present("exposure") {
+        scene.config.exposure =
+            clap::value_t!(matches, "exposure", f64).unwrap_or_else(|e| e.exit());
+    }
+    if matches.is_present("tone-map") {
+        scene.config.tone_map =
+            clap::value_t!(matches, "tone-map", ToneMap).unwrap_or_else(|e| e.exit());
+    }
+    if matches.is_present("transfer") {
+        scene.config.transfer =
+            clap::value_t!(matches, "transfer", Transfer).unwrap_or_else(|e| e.exit());
+    }
 
-    let img = scene.render();
+    if matches.is_present("aov") {
+        scene.config.aovs = matches
+            .values_of_t::<Aov>("aov")
+            .unwrap_or_else(|e| e.exit());
+    }
 
     // `unwrap` is safe as it has a default
     let out_path = matches.value_of("out").unwrap();
-    println!("writing file to {}", &out_path);
-    img.save(&out_path)?;
+
+    let sample_range = if matches.is_present("sample-range") {
+        let range = matches
+            .values_of_t::<u32>("sample-range")
+            .unwrap_or_else(|e| e.exit());
+        // Each pixel's samples are spread out over all of them, so the range
+        // has to be out of the same number of samples in every part.
+        if range[0] >= range[1] || range[1] > scene.config.samples {
+            return Err(format!(
+                "the sample range {},{} isn't part of the {} samples per pixel",
+                range[0], range[1], scene.config.samples
+            )
+            .into());
+        }
+        if scene.config.adaptive.is_some() {
+            return Err("a sample range can't be used with adaptive sampling".into());
+        }
+        Some((range[0], range[1]))
+    } else {
+        None
+    };
+
+    let quiet = matches.is_present("quiet");
+
+    let checkpoint = match matches.value_of("resume") {
+        None if matches.is_present("region") || sample_range.is_some() => {
+            let mut checkpoint = Checkpoint::new(&scene);
+            if matches.is_present("region") {
+                let region = matches
+                    .values_of_t::<u32>("region")
+                    .unwrap_or_else(|e| e.exit());
+                checkpoint = checkpoint.with_region(Tile {
+                    left: region[0],
+                    top: region[1],
+                    right: region[0] + region[2],
+                    bottom: region[1] + region[3],
+                });
+            }
+            if let Some((start, end)) = sample_range {
+                checkpoint = checkpoint.starting_at(start).ending_at(end);
+            }
+            Some(checkpoint)
+        }
+        Some(path) => {
+            let checkpoint = Checkpoint::read(path)?;
+            checkpoint.check(&scene)?;
+            if !quiet {
+                println!(
+                    "resuming from {} after {} samples",
+                    path,
+                    checkpoint.samples()
+                );
+            }
+            Some(checkpoint)
+        }
+        None => None,
+    };
+
+    let snapshots = scene.config.progressive.is_some();
+    let checkpoint_path = matches.value_of("checkpoint");
+    let checkpoint_interval = Duration::from_secs(
+        clap::value_t!(matches, "checkpoint-interval", u64).unwrap_or_else(|e| e.exit()),
+    );
+    let time_limit = if matches.is_present("time-limit") {
+        let limit = clap::value_t!(matches, "time-limit", f64).unwrap_or_else(|e| e.exit());
+        Some(Duration::from_secs_f64(limit.max(0.0)))
+    } else {
+        None
+    };
+    let cancel = match time_limit {
+        Some(limit) => CancelToken::after(limit),
+        None => CancelToken::new(),
+    };
+
+    // Checkpoints can only be saved between passes, and a render stopped at a
+    // time limit should have samples all over the image rather than some
+    // pixels finished and the rest black.
+    if (checkpoint_path.is_some() || time_limit.is_some()) && scene.config.progressive.is_none() {
+        scene.config.progressive = Some(1);
+    }
+
+    let style = if quiet {
+        Style::None
+    } else {
+        clap::value_t!(matches, "progress", Style).unwrap_or_else(|e| e.exit())
+    };
+    let progress: Box<dyn Progress> = match style {
+        Style::Bar => Box::new(progress::Bar::new()),
+        Style::Json => Box::new(progress::Json::new()),
+        Style::None => Box::new(Silent),
+    };
+
+    let mut last_checkpoint = Instant::now();
+    let mut pass_error = None;
+    let framebuffer =
+        scene.render_progressive(checkpoint, &cancel, progress.as_ref(), |snapshot| {
+            if pass_error.is_some() {
+                return;
+            }
+            if snapshots && !snapshot.is_last() {
+                pass_error = write(&snapshot.framebuffer(), &scene.config, out_path, false).err();
+            }
+            if let Some(path) = checkpoint_path {
+                if pass_error.is_none()
+                    && (snapshot.is_last() || last_checkpoint.elapsed() >= checkpoint_interval)
+                {
+                    pass_error = snapshot.checkpoint().write(path).err().map(Into::into);
+                    last_checkpoint = Instant::now();
+                }
+            }
+        });
+    if let Some(e) = pass_error {
+        return Err(e);
+    }
+
+    if cancel.is_cancelled() && !quiet {
+        let counts = framebuffer.sample_counts();
+        let fewest = counts.iter().min().copied().unwrap_or(0);
+        let most = counts.iter().max().copied().unwrap_or(0);
+        println!(
+            "stopped at the time limit, with {} to {} samples per pixel",
+            fewest, most
+        );
+    }
+
+    write(&framebuffer, &scene.config, out_path, !quiet)
+}
+
+/// Merge the checkpoints of renders of parts of an image, and write the
+/// image they make.
+fn merge(matches: &clap::ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    // `unwrap` is safe as it's a required arg.
+    let file = File::open(matches.value_of("scene").unwrap())?;
+    let mut scene: Scene = serde_json::from_reader(file)?;
+    if matches.is_present("aov") {
+        scene.config.aovs = matches
+            .values_of_t::<Aov>("aov")
+            .unwrap_or_else(|e| e.exit());
+    }
+
+    // `unwrap` is safe as it's a required arg.
+    let mut paths = matches.values_of("checkpoints").unwrap();
+    // There's always at least one, as it's required.
+    let first = paths.next().unwrap();
+    let mut merged = Checkpoint::read(first)?;
+    for path in paths {
+        merged
+            .merge(&Checkpoint::read(path)?)
+            .map_err(|e| format!("{}: {}", path, e))?;
+    }
+
+    // The parts could have been rendered at a different size to the scene's,
+    // but otherwise the AOVs have to come from the same scene.
+    let (width, height) = merged.size();
+    scene.config.width = width;
+    scene.config.height = height;
+    merged.check_settings(&scene)?;
+
+    // `unwrap` is safe as it has a default
+    let out_path = matches.value_of("out").unwrap();
+    let report = !matches.is_present("quiet");
+    write(&scene.framebuffer(&merged), &scene.config, out_path, report)
+}
+
+/// Write the image, and its AOVs, in the format given by the path's
+/// extension, saying where each is written if `report` is set.
+fn write(
+    framebuffer: &Framebuffer,
+    config: &Config,
+    out_path: &str,
+    report: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if report {
+        println!("writing file to {}", &out_path);
+    }
+    let extension = Path::new(out_path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_lowercase);
+    match extension.as_deref() {
+        Some("exr") => framebuffer.write_exr(out_path)?,
+        Some("hdr") => framebuffer.write_hdr(out_path)?,
+        Some("pfm") => framebuffer.write_pfm(out_path)?,
+        _ => framebuffer.to_image(config).save(out_path)?,
+    }
+
+    // EXR files already have the AOVs in them, other formats need a file each.
+    if extension.as_deref() != Some("exr") {
+        for aov in framebuffer.aovs() {
+            let path = aov_path(out_path, aov);
+            if report {
+                println!("writing {} to {}", aov, path.display());
+            }
+            // `unwrap` is safe as the AOV came from the framebuffer.
+            match extension.as_deref() {
+                Some("hdr") => framebuffer.aov(aov).unwrap().write_hdr(&path)?,
+                Some("pfm") => framebuffer.aov(aov).unwrap().write_pfm(&path)?,
+                _ => framebuffer.aov_image(aov).unwrap().save(&path)?,
+            }
+        }
+    }
     Ok(())
 }
+
+/// Where to write an AOV, next to the image with the AOV's name before the
+/// extension.
+fn aov_path(out_path: &str, aov: Aov) -> PathBuf {
+    let path = Path::new(out_path);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    let name = match path.extension().and_then(|e| e.to_str()) {
+        Some(extension) => format!("{}.{}.{}", stem, aov, extension),
+        None => format!("{}.{}", stem, aov),
+    };
+    path.with_file_name(name)
+}
@@ -0,0 +1,135 @@
+//! The ways the command line shows how a render is getting on.
+
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use mobula::progress::Progress;
+
+/// How to show progress.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Style {
+    /// A bar drawn in the terminal.
+    Bar,
+    /// A JSON object per line, for other programs to read.
+    Json,
+    /// Nothing.
+    None,
+}
+
+impl Style {
+    pub const NAMES: [&'static str; 3] = ["bar", "json", "none"];
+}
+
+impl FromStr for Style {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bar" => Ok(Style::Bar),
+            "json" => Ok(Style::Json),
+            "none" => Ok(Style::None),
+            _ => Err(format!("unknown progress style '{}'", s)),
+        }
+    }
+}
+
+/// A progress bar, counting tiles.
+pub struct Bar(indicatif::ProgressBar);
+
+impl Bar {
+    pub fn new() -> Self {
+        let bar = indicatif::ProgressBar::new(0);
+        bar.set_style(
+            indicatif::ProgressStyle::default_bar()
+                .template("{msg} {elapsed} [{wide_bar}] {eta_precise} ({pos}/{len} tiles)")
+                .progress_chars("=> "),
+        );
+        Bar(bar)
+    }
+}
+
+impl Progress for Bar {
+    fn start(&self, tiles: u64) {
+        self.0.set_length(tiles);
+        self.0.set_message("rendering");
+    }
+
+    fn pass(&self, pass: u32, passes: u32) {
+        if passes > 1 {
+            self.0.set_message(&format!("pass {}/{}", pass + 1, passes));
+        }
+    }
+
+    fn tile(&self) {
+        self.0.inc(1);
+    }
+
+    fn finish(&self, cancelled: bool) {
+        self.0.finish_with_message(if cancelled {
+            "stopped after"
+        } else {
+            "complete in"
+        });
+    }
+}
+
+/// Writes each event as a line of JSON to stderr, like
+/// `{"event":"tile","done":12,"tiles":40,"elapsed":1.5}`.
+pub struct Json {
+    started: Instant,
+    tiles: AtomicU64,
+    done: AtomicU64,
+}
+
+impl Json {
+    pub fn new() -> Self {
+        Json {
+            started: Instant::now(),
+            tiles: AtomicU64::new(0),
+            done: AtomicU64::new(0),
+        }
+    }
+
+    fn emit(&self, event: serde_json::Value) {
+        eprintln!("{}", event);
+    }
+
+    fn elapsed(&self) -> f64 {
+        self.started.elapsed().as_secs_f64()
+    }
+}
+
+impl Progress for Json {
+    fn start(&self, tiles: u64) {
+        self.tiles.store(tiles, Ordering::Relaxed);
+        self.emit(serde_json::json!({ "event": "start", "tiles": tiles }));
+    }
+
+    fn pass(&self, pass: u32, passes: u32) {
+        self.emit(serde_json::json!({
+            "event": "pass",
+            "pass": pass + 1,
+            "passes": passes,
+            "elapsed": self.elapsed(),
+        }));
+    }
+
+    fn tile(&self) {
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        self.emit(serde_json::json!({
+            "event": "tile",
+            "done": done,
+            "tiles": self.tiles.load(Ordering::Relaxed),
+            "elapsed": self.elapsed(),
+        }));
+    }
+
+    fn finish(&self, cancelled: bool) {
+        self.emit(serde_json::json!({
+            "event": "finish",
+            "cancelled": cancelled,
+            "elapsed": self.elapsed(),
+        }));
+    }
+}
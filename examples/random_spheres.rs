@@ -0,0 +1,103 @@
+//! A benchmark scene made of lots of small random spheres.
+//!
+//! Renders the same view with more and more spheres and prints how long each
+//! one took, which should grow roughly logarithmically with the number of
+//! objects. Pass the counts to try as arguments, for example:
+//!
+//! ```sh
+//! $ cargo run --release --example random_spheres -- 100 1000 10000 100000
+//! ```
+//!
+//! With `--json N` it instead prints a scene with `N` spheres, which can be
+//! rendered with the `mobula` binary.
+
+use std::time::Instant;
+
+use rand::{Rng, SeedableRng};
+
+use mobula::camera::CameraBuilder;
+use mobula::config::Config;
+use mobula::material::Material;
+use mobula::point::Point;
+use mobula::scene::Scene;
+use mobula::shape::sphere::Sphere;
+use mobula::shape::Shape;
+
+const DEFAULT_COUNTS: [usize; 3] = [100, 1_000, 10_000];
+
+fn random_spheres(count: usize) -> Scene {
+    // A fixed seed so every run builds the same scene.
+    let mut rng = rand::rngs::StdRng::seed_from_u64(0x6d6f62756c61);
+
+    let config = Config {
+        width: 200,
+        height: 150,
+        depth: 4,
+        samples: 2,
+        ..Config::default()
+    };
+
+    let camera = CameraBuilder::new()
+        .origin(Point::new(0.0, 20.0, 60.0))
+        .target(Point::new(0.0, 0.0, 0.0))
+        .fov(45.0)
+        .aperture(0.0);
+
+    let ground = Sphere::new(
+        Point::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Material::lambertian(0.5, 0.5, 0.5),
+    );
+
+    let mut scene = Scene::new()
+        .config(config)
+        .camera(camera)
+        .push(Shape::Sphere(ground));
+
+    for _ in 0..count {
+        let radius = rng.gen_range(0.1, 0.4);
+        let centre = Point::new(
+            rng.gen_range(-50.0, 50.0),
+            radius,
+            rng.gen_range(-50.0, 50.0),
+        );
+        let material = if rng.gen_bool(0.8) {
+            Material::lambertian(rng.gen(), rng.gen(), rng.gen())
+        } else {
+            Material::metal(rng.gen(), rng.gen(), rng.gen(), rng.gen_range(0.0, 0.5))
+        };
+        scene = scene.push(Shape::Sphere(Sphere::new(centre, radius, material)));
+    }
+
+    scene
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    if args.first().map(String::as_str) == Some("--json") {
+        let count = match args.get(1) {
+            Some(n) => n.parse()?,
+            None => DEFAULT_COUNTS[DEFAULT_COUNTS.len() - 1],
+        };
+        println!("{}", serde_json::to_string(&random_spheres(count))?);
+        return Ok(());
+    }
+
+    let counts = if args.is_empty() {
+        DEFAULT_COUNTS.to_vec()
+    } else {
+        args.iter()
+            .map(|n| n.parse())
+            .collect::<Result<Vec<usize>, _>>()?
+    };
+
+    for count in counts {
+        let scene = random_spheres(count);
+        let start = Instant::now();
+        scene.render();
+        println!("{:>8} spheres: {:?}", count, start.elapsed());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,142 @@
+//! A scene lit only by a small, bright sphere and a small triangle, which is
+//! very noisy unless shadow rays are traced to the lights.
+//!
+//! Renders a reference image with lots of samples, then the same number of
+//! samples with and without light sampling, and prints how far each is from
+//! the reference. Pass the samples per pixel to try, for example:
+//!
+//! ```sh
+//! $ cargo run --release --example small_light -- 4 16 64
+//! ```
+//!
+//! With `--json` it instead prints the scene, which can be rendered with the
+//! `mobula` binary.
+
+use std::time::Instant;
+
+use mobula::background::Background;
+use mobula::camera::CameraBuilder;
+use mobula::config::Config;
+use mobula::framebuffer::Framebuffer;
+use mobula::material::Material;
+use mobula::point::Point;
+use mobula::scene::Scene;
+use mobula::shape::sphere::Sphere;
+use mobula::shape::triangle::Triangle;
+use mobula::shape::Shape;
+
+const DEFAULT_SAMPLES: [u32; 3] = [4, 16, 64];
+const REFERENCE_SAMPLES: u32 = 2048;
+
+fn small_light(samples: u32, light_sampling: bool) -> Scene {
+    let config = Config {
+        width: 160,
+        height: 120,
+        depth: 4,
+        samples,
+        light_sampling,
+        ..Config::default()
+    };
+
+    let camera = CameraBuilder::new()
+        .origin(Point::new(0.0, 1.5, 5.0))
+        .target(Point::new(0.0, 0.5, 0.0))
+        .fov(40.0)
+        .aperture(0.0);
+
+    let ground = Sphere::new(
+        Point::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Material::lambertian(0.5, 0.5, 0.5),
+    );
+    let lamp = Sphere::new(
+        Point::new(-1.0, 2.5, 0.5),
+        0.05,
+        Material::diffuse_light(1.0, 0.9, 0.7, 2000.0),
+    );
+    let panel = Triangle::new(
+        Point::new(1.5, 3.0, -0.5),
+        Point::new(1.8, 3.0, -0.5),
+        Point::new(1.5, 3.0, -0.2),
+        Material::diffuse_light(0.6, 0.7, 1.0, 200.0),
+    );
+
+    Scene::new()
+        .config(config)
+        .camera(camera)
+        .background(Background::None)
+        .push(Shape::Sphere(ground))
+        .push(Shape::Sphere(Sphere::new(
+            Point::new(-1.2, 0.5, 0.0),
+            0.5,
+            Material::lambertian(0.8, 0.3, 0.3),
+        )))
+        .push(Shape::Sphere(Sphere::new(
+            Point::new(0.0, 0.5, -0.5),
+            0.5,
+            Material::lambertian(0.3, 0.8, 0.3),
+        )))
+        .push(Shape::Sphere(Sphere::new(
+            Point::new(1.2, 0.5, 0.0),
+            0.5,
+            Material::lambertian(0.3, 0.3, 0.8),
+        )))
+        .push(Shape::Sphere(lamp))
+        .push(Shape::Triangle(panel))
+}
+
+/// The root mean square difference between two images, over every channel.
+fn rms_error(image: &Framebuffer, reference: &Framebuffer) -> f64 {
+    let sum: f64 = image
+        .pixels()
+        .iter()
+        .zip(reference.pixels())
+        .map(|(a, b)| (a.r - b.r).powi(2) + (a.g - b.g).powi(2) + (a.b - b.b).powi(2))
+        .sum();
+    (sum / (3 * image.pixels().len()) as f64).sqrt()
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    if args.first().map(String::as_str) == Some("--json") {
+        let samples = match args.get(1) {
+            Some(n) => n.parse()?,
+            None => DEFAULT_SAMPLES[DEFAULT_SAMPLES.len() - 1],
+        };
+        println!("{}", serde_json::to_string(&small_light(samples, true))?);
+        return Ok(());
+    }
+
+    let samples = if args.is_empty() {
+        DEFAULT_SAMPLES.to_vec()
+    } else {
+        args.iter()
+            .map(|n| n.parse())
+            .collect::<Result<Vec<u32>, _>>()?
+    };
+
+    let start = Instant::now();
+    let reference = small_light(REFERENCE_SAMPLES, true).render_par();
+    println!(
+        "reference at {} samples: {:?}",
+        REFERENCE_SAMPLES,
+        start.elapsed()
+    );
+
+    for count in samples {
+        for &light_sampling in &[false, true] {
+            let start = Instant::now();
+            let image = small_light(count, light_sampling).render_par();
+            println!(
+                "{:>6} samples, light sampling {:<5}: error {:.4} in {:?}",
+                count,
+                light_sampling,
+                rms_error(&image, &reference),
+                start.elapsed()
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,116 @@
+use crate::point::Point;
+use crate::v3::V3;
+
+/// An axis-aligned bounding box.
+#[derive(Copy, Clone, Debug)]
+pub struct Aabb {
+    pub min: V3,
+    pub max: V3,
+}
+
+impl Aabb {
+    pub fn new(min: V3, max: V3) -> Self {
+        Aabb { min, max }
+    }
+
+    /// A box containing nothing, which is the identity for `union`.
+    pub fn empty() -> Self {
+        Aabb {
+            min: V3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            max: V3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        }
+    }
+
+    /// The smallest box containing both boxes.
+    pub fn union(self, other: Aabb) -> Aabb {
+        Aabb {
+            min: V3::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max: V3::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        }
+    }
+
+    /// The smallest box containing both this box and a point.
+    pub fn grow(self, point: Point) -> Aabb {
+        let p = V3::from(point);
+        self.union(Aabb::new(p, p))
+    }
+
+    pub fn centroid(self) -> V3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    /// The surface area of the box, which is what the surface area heuristic
+    /// uses as the probability of a random ray hitting it.
+    pub fn surface_area(self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let d = self.max - self.min;
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    /// The component of a vector along an axis, where 0 is x, 1 is y and 2 is z.
+    pub fn axis(v: V3, axis: usize) -> f64 {
+        match axis {
+            0 => v.x,
+            1 => v.y,
+            _ => v.z,
+        }
+    }
+
+    /// The index of the axis the box is longest along.
+    pub fn longest_axis(self) -> usize {
+        let d = self.max - self.min;
+        if d.x > d.y && d.x > d.z {
+            0
+        } else if d.y > d.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// The slab test. The ray is given as an origin and the reciprocal of its
+    /// direction so the division is done once per ray rather than per box.
+    ///
+    /// Returns the parameter at which the ray enters the box, if it does so
+    /// between `t_min` and `t_max`.
+    pub fn is_hit_by(
+        self,
+        origin: V3,
+        inverse_direction: V3,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<f64> {
+        let mut t_min = t_min;
+        let mut t_max = t_max;
+        for axis in 0..3 {
+            let inv = Aabb::axis(inverse_direction, axis);
+            let o = Aabb::axis(origin, axis);
+            let mut t0 = (Aabb::axis(self.min, axis) - o) * inv;
+            let mut t1 = (Aabb::axis(self.max, axis) - o) * inv;
+            if inv < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // Written so that a NaN from `0 * inf` leaves the bounds alone.
+            t_min = if t0 > t_min { t0 } else { t_min };
+            t_max = if t1 < t_max { t1 } else { t_max };
+            if t_max < t_min {
+                return None;
+            }
+        }
+        Some(t_min)
+    }
+}
@@ -0,0 +1,100 @@
+//! Adaptive sampling, which keeps taking samples of a pixel until the noise
+//! in it is small enough, rather than taking the same number everywhere.
+//!
+//! Each pixel first gets the config's `samples`, then more one at a time
+//! until the estimated error of its mean is below the target or it reaches
+//! `max_samples`. Flat areas like the sky stop almost straight away, leaving
+//! the time for soft shadows, caustics and glossy reflections.
+
+use serde::{Deserialize, Serialize};
+
+use crate::colour::Colour;
+
+/// When to stop sampling a pixel.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Adaptive {
+    /// The most samples a pixel can get.
+    #[serde(default = "Adaptive::default_max_samples")]
+    pub max_samples: u32,
+    /// The standard error of a pixel's mean, relative to its brightness, to
+    /// stop at.
+    #[serde(default = "Adaptive::default_error")]
+    pub error: f64,
+}
+
+impl Default for Adaptive {
+    fn default() -> Self {
+        Adaptive {
+            max_samples: Adaptive::default_max_samples(),
+            error: Adaptive::default_error(),
+        }
+    }
+}
+
+impl Adaptive {
+    fn default_max_samples() -> u32 {
+        256
+    }
+
+    fn default_error() -> f64 {
+        0.02
+    }
+}
+
+/// Pixels darker than this are treated as this bright when working out their
+/// relative error, so black pixels don't need endless samples.
+const MIN_BRIGHTNESS: f64 = 0.05;
+
+/// The running mean and variance of the brightness of a pixel's samples,
+/// using Welford's method.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Estimate {
+    pub(crate) count: u32,
+    pub(crate) mean: f64,
+    // The sum of squared differences from the mean.
+    pub(crate) m2: f64,
+}
+
+impl Estimate {
+    pub fn add(&mut self, colour: Colour) {
+        let x = colour.luminance();
+        self.count += 1;
+        let delta = x - self.mean;
+        self.mean += delta / f64::from(self.count);
+        self.m2 += delta * (x - self.mean);
+    }
+
+    /// The estimate for the samples of both, using Chan et al.'s way of
+    /// combining variances.
+    pub fn merge(self, other: Estimate) -> Estimate {
+        if self.count == 0 {
+            return other;
+        }
+        if other.count == 0 {
+            return self;
+        }
+        let (a, b) = (f64::from(self.count), f64::from(other.count));
+        let count = self.count + other.count;
+        let n = a + b;
+        let delta = other.mean - self.mean;
+        Estimate {
+            count,
+            mean: self.mean + delta * b / n,
+            m2: self.m2 + other.m2 + delta * delta * a * b / n,
+        }
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// The standard error of the mean, relative to the mean.
+    pub fn relative_error(&self) -> f64 {
+        if self.count < 2 {
+            return f64::INFINITY;
+        }
+        let n = f64::from(self.count);
+        let variance = self.m2 / (n - 1.0);
+        (variance / n).sqrt() / self.mean.max(MIN_BRIGHTNESS)
+    }
+}
@@ -0,0 +1,138 @@
+//! Arbitrary output variables, extra images rendered alongside the beauty
+//! image for denoising, compositing, and debugging scenes.
+
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::colour::Colour;
+use crate::tone_map::Transfer;
+
+/// The passes that can be rendered. Most are taken from the first surface
+/// seen through a pixel.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Aov {
+    /// The distance to the surface along the camera's view direction, or
+    /// infinity for the background. This is the nearest of a pixel's samples.
+    Depth,
+    /// The world space normal of the surface, averaged over the samples.
+    Normal,
+    /// The colour of the surface ignoring lighting, averaged over the samples.
+    Albedo,
+    /// A number for each distinct material, in the order they first appear in
+    /// the image, starting at 1. The background is 0.
+    MaterialId,
+    /// One more than the index of the object in the scene, so the background
+    /// can be 0.
+    ObjectId,
+    /// How many samples the pixel got, which only varies with adaptive
+    /// sampling.
+    SampleCount,
+}
+
+impl Aov {
+    pub const NAMES: [&'static str; 6] = [
+        "depth",
+        "normal",
+        "albedo",
+        "material_id",
+        "object_id",
+        "sample_count",
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::Albedo => "albedo",
+            Aov::MaterialId => "material_id",
+            Aov::ObjectId => "object_id",
+            Aov::SampleCount => "sample_count",
+        }
+    }
+
+    /// The channels the pass is written to in EXR files. Passes with a single
+    /// channel keep their value in all three of a colour's channels.
+    pub fn channels(self) -> &'static [&'static str] {
+        match self {
+            Aov::Depth => &["Z"],
+            Aov::Normal => &["X", "Y", "Z"],
+            Aov::Albedo => &["R", "G", "B"],
+            Aov::MaterialId | Aov::ObjectId => &["id"],
+            Aov::SampleCount => &["N"],
+        }
+    }
+
+    /// Turn a pass's value into a colour that can be seen in an 8-bit image.
+    /// `largest` is the largest finite value in the pass.
+    pub fn display_colour(self, value: Colour, largest: f64) -> Colour {
+        match self {
+            Aov::Depth if value.r.is_finite() && largest > 0.0 => {
+                let near = 1.0 - value.r / largest;
+                Colour::new(near, near, near).clamp()
+            }
+            Aov::Depth => Colour::black(),
+            Aov::Normal => Colour {
+                r: value.r * 0.5 + 0.5,
+                g: value.g * 0.5 + 0.5,
+                b: value.b * 0.5 + 0.5,
+            }
+            .clamp(),
+            Aov::Albedo => Transfer::Srgb.encode(value.clamp()),
+            Aov::MaterialId | Aov::ObjectId => id_colour(value.r as u32),
+            Aov::SampleCount if largest > 0.0 => heat_colour(value.r / largest),
+            Aov::SampleCount => Colour::black(),
+        }
+    }
+}
+
+/// A heat map colour for `t` in [0, 1], going from black through red and
+/// yellow to white.
+fn heat_colour(t: f64) -> Colour {
+    let t = t.clamp(0.0, 1.0) * 3.0;
+    Colour::new(t, t - 1.0, t - 2.0).clamp()
+}
+
+/// A colour for an ID, with neighbouring IDs given very different hues.
+fn id_colour(id: u32) -> Colour {
+    if id == 0 {
+        return Colour::black();
+    }
+    // Stepping round the colour wheel by the golden ratio never comes back
+    // near a hue it's already used.
+    let hue = (id as f64 * 0.618_033_988_749_895).fract() * 6.0;
+    let x = 1.0 - (hue % 2.0 - 1.0).abs();
+    let (r, g, b) = match hue as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    Colour::new(r, g, b) * 0.8 + Colour::new(0.1, 0.1, 0.1)
+}
+
+impl fmt::Display for Aov {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Aov {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "depth" => Ok(Aov::Depth),
+            "normal" => Ok(Aov::Normal),
+            "albedo" => Ok(Aov::Albedo),
+            "material_id" => Ok(Aov::MaterialId),
+            "object_id" => Ok(Aov::ObjectId),
+            "sample_count" => Ok(Aov::SampleCount),
+            _ => Err(format!("unknown AOV '{}'", s)),
+        }
+    }
+}
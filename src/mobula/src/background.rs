@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+
+use crate::colour::Colour;
+use crate::environment::Environment;
+use crate::light::Light;
+use crate::ray::Ray;
+use crate::sky::Sky;
+use crate::v3::V3;
+
+/// The light coming from behind everything, seen by rays which miss all the
+/// objects in the scene.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+#[serde(rename_all = "snake_case")]
+pub enum Background {
+    /// The same colour in every direction.
+    Solid { colour: Colour },
+    /// A blend from the `horizon` colour to the `zenith` colour as rays point
+    /// closer to `up`. It's mirrored below the horizon.
+    Gradient {
+        horizon: Colour,
+        zenith: Colour,
+        #[serde(default = "Background::default_up")]
+        up: V3,
+    },
+    /// Light from an HDR environment map.
+    Environment(Environment),
+    /// A daylight sky, with the sun as a light.
+    Sky(Sky),
+    /// No light at all, so the only light is from emissive materials.
+    #[serde(alias = "black")]
+    None,
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::Gradient {
+            horizon: Colour::white(),
+            zenith: Colour::new(0.5, 0.7, 1.0),
+            up: Background::default_up(),
+        }
+    }
+}
+
+impl Background {
+    fn default_up() -> V3 {
+        V3::new(0.0, 1.0, 0.0)
+    }
+
+    pub fn colour(&self, ray: &Ray) -> Colour {
+        match self {
+            Background::Solid { colour } => *colour,
+            Background::Gradient {
+                horizon,
+                zenith,
+                up,
+            } => {
+                let unit = ray.direction().normalize();
+                let t = unit.dot(up.normalize()).abs().min(1.0);
+                Colour::linear_interpolation(*horizon, *zenith, t)
+            }
+            Background::Environment(e) => e.radiance(ray.direction()),
+            Background::Sky(s) => s.radiance(ray.direction()),
+            Background::None => Colour::black(),
+        }
+    }
+
+    /// The sun that comes with a sky, which lights the scene like any other
+    /// directional light.
+    pub fn sun(&self) -> Option<Light> {
+        match self {
+            Background::Sky(s) => s.sun(),
+            _ => None,
+        }
+    }
+
+    /// Backgrounds with small bright spots, like the sun in an environment
+    /// map, are found much faster by picking directions towards them than by
+    /// waiting for scattered rays to hit them. This is true for backgrounds
+    /// which `sample` can do that for.
+    pub fn can_be_sampled(&self) -> bool {
+        matches!(self, Background::Environment(_))
+    }
+
+    /// Pick a direction light comes from, returning the direction, the light,
+    /// and the probability density of picking that direction.
+    pub fn sample(&self, u: (f64, f64)) -> Option<(V3, Colour, f64)> {
+        match self {
+            Background::Environment(e) => e.sample(u),
+            _ => None,
+        }
+    }
+
+    /// The probability density of `sample` picking a direction.
+    pub fn pdf(&self, direction: V3) -> f64 {
+        match self {
+            Background::Environment(e) => e.pdf(direction),
+            _ => 0.0,
+        }
+    }
+}
@@ -0,0 +1,321 @@
+//! A bounding volume hierarchy, used so finding the nearest hit doesn't need
+//! to test a ray against every object in a scene.
+//!
+//! The tree is built top-down using a binned surface area heuristic, and is
+//! stored flattened into a single array of nodes in depth-first order, so the
+//! first child of an interior node is always the node right after it.
+
+use crate::aabb::Aabb;
+use crate::hit::{Hit, Hitable};
+use crate::ray::Ray;
+use crate::v3::V3;
+
+/// The number of buckets centroids are sorted into when looking for a split.
+const BINS: usize = 16;
+
+/// Nodes with at most this many items become leaves if splitting doesn't
+/// look cheaper.
+const MAX_LEAF_SIZE: usize = 4;
+
+/// The cost of visiting a node, relative to the cost of testing one item.
+const TRAVERSAL_COST: f64 = 0.125;
+
+#[derive(Copy, Clone, Debug)]
+struct Node {
+    bounds: Aabb,
+    // For leaves, this is the index of the first item. For interior nodes it's
+    // the index of the second child.
+    offset: usize,
+    // The number of items in a leaf, interior nodes have none.
+    count: usize,
+    // The axis interior nodes were split along.
+    axis: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct Bvh<T> {
+    items: Vec<T>,
+    // Where each item was in the list the tree was built from.
+    indices: Vec<usize>,
+    nodes: Vec<Node>,
+}
+
+impl<T: Hitable> Bvh<T> {
+    pub fn new(items: Vec<T>) -> Self {
+        let bounds: Vec<Aabb> = items.iter().map(Hitable::bounds).collect();
+        Bvh::from_bounds(items, &bounds)
+    }
+}
+
+impl<T> Bvh<T> {
+    /// Build a tree over items which can't find their own bounds, such as the
+    /// faces of a mesh which need the mesh's vertices.
+    pub fn from_bounds(items: Vec<T>, bounds: &[Aabb]) -> Self {
+        debug_assert_eq!(items.len(), bounds.len());
+        let centroids: Vec<V3> = bounds.iter().map(|b| b.centroid()).collect();
+        let mut order: Vec<usize> = (0..items.len()).collect();
+
+        let mut builder = Builder {
+            bounds,
+            centroids: &centroids,
+            nodes: Vec::with_capacity(2 * items.len()),
+        };
+
+        if !items.is_empty() {
+            builder.build(&mut order, 0);
+        }
+        let nodes = builder.nodes;
+
+        // Put the items in the order the leaves expect them.
+        let mut slots: Vec<Option<T>> = items.into_iter().map(Some).collect();
+        let items = order.iter().map(|&i| slots[i].take().unwrap()).collect();
+
+        Bvh {
+            items,
+            indices: order,
+            nodes,
+        }
+    }
+
+    /// The items in the tree, in the order the tree keeps them.
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    /// The items in the order of the list the tree was built from.
+    pub fn original_items(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        let mut slots = vec![0; self.items.len()];
+        for (slot, &index) in self.indices.iter().enumerate() {
+            slots[index] = slot;
+        }
+        slots.iter().map(|&slot| self.items[slot].clone()).collect()
+    }
+
+    /// A box around everything in the tree.
+    pub fn bounds(&self) -> Aabb {
+        self.nodes
+            .first()
+            .map(|root| root.bounds)
+            .unwrap_or_else(Aabb::empty)
+    }
+
+    /// Find the nearest hit along a ray, using `hit_item` to test the items in
+    /// each leaf the ray passes through.
+    ///
+    /// `hit_item` is passed the item's index in the list the tree was built
+    /// from, the item, and the parameter of the nearest hit found so far,
+    /// which it should use as its `t_max`.
+    pub fn traverse<F>(&self, ray: &Ray, t_min: f64, t_max: f64, mut hit_item: F) -> Option<Hit>
+    where
+        F: FnMut(usize, &T, f64) -> Option<Hit>,
+    {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let origin = V3::from(ray.origin());
+        let d = ray.direction();
+        let inverse_direction = V3::new(1.0 / d.x, 1.0 / d.y, 1.0 / d.z);
+        let negative = [d.x < 0.0, d.y < 0.0, d.z < 0.0];
+
+        let mut hit = None;
+        let mut closest_so_far = t_max;
+        let mut stack = Vec::with_capacity(64);
+        let mut current = 0;
+
+        loop {
+            let node = &self.nodes[current];
+            let visit = node
+                .bounds
+                .is_hit_by(origin, inverse_direction, t_min, closest_so_far)
+                .is_some();
+
+            if visit && node.count == 0 {
+                // Visit the child nearer the ray's origin first, so we're more
+                // likely to be able to skip the other.
+                if negative[node.axis] {
+                    stack.push(current + 1);
+                    current = node.offset;
+                } else {
+                    stack.push(node.offset);
+                    current += 1;
+                }
+                continue;
+            }
+
+            if visit {
+                for i in node.offset..node.offset + node.count {
+                    if let Some(nearer_hit) =
+                        hit_item(self.indices[i], &self.items[i], closest_so_far)
+                    {
+                        closest_so_far = nearer_hit.t;
+                        hit = Some(nearer_hit);
+                    }
+                }
+            }
+
+            match stack.pop() {
+                Some(next) => current = next,
+                None => break,
+            }
+        }
+
+        hit
+    }
+}
+
+impl<T: Hitable> Hitable for Bvh<T> {
+    fn is_hit_by(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        self.traverse(ray, t_min, t_max, |_, item, closest_so_far| {
+            item.is_hit_by(ray, t_min, closest_so_far)
+        })
+    }
+
+    fn bounds(&self) -> Aabb {
+        // The inherent method, which doesn't need the items to be `Hitable`.
+        Bvh::bounds(self)
+    }
+}
+
+struct Builder<'a> {
+    bounds: &'a [Aabb],
+    centroids: &'a [V3],
+    nodes: Vec<Node>,
+}
+
+#[derive(Copy, Clone)]
+struct Bin {
+    count: usize,
+    bounds: Aabb,
+}
+
+impl<'a> Builder<'a> {
+    /// Build the subtree for the items in `order`, which start at `first` in
+    /// the final item order. Returns the index of the subtree's root.
+    fn build(&mut self, order: &mut [usize], first: usize) -> usize {
+        let index = self.nodes.len();
+        let bounds = order
+            .iter()
+            .fold(Aabb::empty(), |b, &i| b.union(self.bounds[i]));
+
+        self.nodes.push(Node {
+            bounds,
+            offset: first,
+            count: order.len(),
+            axis: 0,
+        });
+
+        if order.len() == 1 {
+            return index;
+        }
+
+        let centroid_bounds = order.iter().fold(Aabb::empty(), |b, &i| {
+            b.union(Aabb::new(self.centroids[i], self.centroids[i]))
+        });
+        let axis = centroid_bounds.longest_axis();
+        let low = Aabb::axis(centroid_bounds.min, axis);
+        let extent = Aabb::axis(centroid_bounds.max, axis) - low;
+
+        // If all the centroids are in the same place there's no way to split
+        // them up, so this has to be a leaf no matter how big it is.
+        if extent <= 0.0 {
+            return index;
+        }
+
+        let bin_of = |i: usize| {
+            let c = Aabb::axis(self.centroids[i], axis);
+            (((c - low) / extent * BINS as f64) as usize).min(BINS - 1)
+        };
+
+        let mut bins = [Bin {
+            count: 0,
+            bounds: Aabb::empty(),
+        }; BINS];
+        for &i in order.iter() {
+            let bin = &mut bins[bin_of(i)];
+            bin.count += 1;
+            bin.bounds = bin.bounds.union(self.bounds[i]);
+        }
+
+        // The cost of splitting after each bin, sweeping in from both ends so
+        // it's linear in the number of bins.
+        let mut costs = [0.0; BINS - 1];
+        let mut left = Bin {
+            count: 0,
+            bounds: Aabb::empty(),
+        };
+        for (split, bin) in bins[..BINS - 1].iter().enumerate() {
+            left.count += bin.count;
+            left.bounds = left.bounds.union(bin.bounds);
+            costs[split] = left.count as f64 * left.bounds.surface_area();
+        }
+        let mut right = Bin {
+            count: 0,
+            bounds: Aabb::empty(),
+        };
+        for split in (0..BINS - 1).rev() {
+            let bin = bins[split + 1];
+            right.count += bin.count;
+            right.bounds = right.bounds.union(bin.bounds);
+            costs[split] += right.count as f64 * right.bounds.surface_area();
+        }
+
+        let (split, split_cost) =
+            costs
+                .iter()
+                .enumerate()
+                .fold((0, f64::INFINITY), |best, (split, &cost)| {
+                    if cost < best.1 {
+                        (split, cost)
+                    } else {
+                        best
+                    }
+                });
+        let split_cost = TRAVERSAL_COST + split_cost / bounds.surface_area();
+        let leaf_cost = order.len() as f64;
+
+        if order.len() <= MAX_LEAF_SIZE && leaf_cost <= split_cost {
+            return index;
+        }
+
+        let mut mid = partition(order, |i| bin_of(i) <= split);
+
+        // Binning can fail to separate anything when the centroids are
+        // clumped, in which case falling back to a median split keeps the tree
+        // from degenerating.
+        if mid == 0 || mid == order.len() {
+            mid = order.len() / 2;
+            let centroids = self.centroids;
+            order.select_nth_unstable_by(mid, |&a, &b| {
+                let a = Aabb::axis(centroids[a], axis);
+                let b = Aabb::axis(centroids[b], axis);
+                a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+            });
+        }
+
+        let (left, right) = order.split_at_mut(mid);
+        self.build(left, first);
+        let second = self.build(right, first + mid);
+
+        self.nodes[index].offset = second;
+        self.nodes[index].count = 0;
+        self.nodes[index].axis = axis;
+        index
+    }
+}
+
+/// Move the items matching the predicate to the front of the slice, returning
+/// how many there are.
+fn partition<F: Fn(usize) -> bool>(order: &mut [usize], predicate: F) -> usize {
+    let mut mid = 0;
+    for i in 0..order.len() {
+        if predicate(order[i]) {
+            order.swap(i, mid);
+            mid += 1;
+        }
+    }
+    mid
+}
@@ -1,19 +1,26 @@
-use rand::random;
 use serde::{Deserialize, Serialize};
 
 use crate::config::Config;
 use crate::point::Point;
 use crate::ray::Ray;
+use crate::sampler::SampleStream;
 use crate::v3::V3;
 
 use std::f64::consts::PI;
 
-fn random_in_unit_disk() -> V3 {
-    let mut p = V3::new(10.0, 0.0, 0.0);
-    while p.magnitude() >= 1.0 {
-        p = (V3::new(random(), random(), 0.0) - V3::new(1.0, 1.0, 0.0)) * 2.0;
+/// Map a point in [0, 1)² to a point in the unit disk with Shirley's concentric
+/// mapping, which keeps points that were evenly spread out that way.
+fn in_unit_disk((u, v): (f64, f64)) -> V3 {
+    let (a, b) = (2.0 * u - 1.0, 2.0 * v - 1.0);
+    if a == 0.0 && b == 0.0 {
+        return V3::zero();
     }
-    p
+    let (r, phi) = if a * a > b * b {
+        (a, (PI / 4.0) * (b / a))
+    } else {
+        (b, PI / 2.0 - (PI / 4.0) * (a / b))
+    };
+    V3::new(r * phi.cos(), r * phi.sin(), 0.0)
 }
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
@@ -80,6 +87,7 @@ impl CameraBuilder {
             origin: self.origin,
             u,
             v,
+            w,
             horizontal: u * (2.0 * half_width * self.auto_focus_distance()),
             vertical: v * (2.0 * half_height * self.auto_focus_distance()),
             lower_left_corner: Point::from(
@@ -135,12 +143,13 @@ pub struct Camera {
     vertical: V3,
     u: V3,
     v: V3,
+    w: V3,
     lens_radius: f64,
 }
 
 impl Camera {
-    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
-        let rd = random_in_unit_disk() * self.lens_radius;
+    pub fn get_ray(&self, s: f64, t: f64, samples: &mut SampleStream) -> Ray {
+        let rd = in_unit_disk(samples.next_2d()) * self.lens_radius;
         let offset = (self.u * rd.x) + (self.v * rd.y);
         Ray::new(
             self.origin.translate(offset),
@@ -149,4 +158,10 @@ impl Camera {
                 - offset,
         )
     }
+
+    /// How far in front of the camera the point at `t` along one of its rays
+    /// is, measured along the direction the camera is looking.
+    pub fn depth(&self, ray: &Ray, t: f64) -> f64 {
+        -(ray.direction() * t).dot(self.w)
+    }
 }
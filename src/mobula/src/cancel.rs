@@ -0,0 +1,49 @@
+//! Stopping a render early, either when asked to or at a deadline.
+//!
+//! A render checks its token before every sample, and when it's cancelled
+//! stops and returns the image made from the samples it has so far.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Shared between a render and whatever wants to stop it. Clones of a token
+/// all cancel together.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+}
+
+impl CancelToken {
+    /// A token that's only cancelled by calling `cancel`.
+    pub fn new() -> Self {
+        CancelToken::default()
+    }
+
+    /// A token that's also cancelled once `limit` has passed.
+    pub fn after(limit: Duration) -> Self {
+        CancelToken {
+            cancelled: Arc::default(),
+            deadline: Instant::now().checked_add(limit),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        if self.cancelled.load(Ordering::Relaxed) {
+            return true;
+        }
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => {
+                // Remember it, so other threads sharing it stop too.
+                self.cancel();
+                true
+            }
+            _ => false,
+        }
+    }
+}
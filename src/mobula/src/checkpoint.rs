@@ -0,0 +1,685 @@
+//! Saving a render part way through, so it can be picked up again later or
+//! put together with renders of other parts of the image.
+//!
+//! A checkpoint has the filtered sums of every sample taken so far, each
+//! pixel's adaptive sampling estimate, and which samples of which pixels have
+//! been taken. Each sample's random numbers depend on the seed, the pixel and
+//! the sample's index, so carrying on from that index with the same number of
+//! samples per pixel gives the same samples the render would have taken if
+//! it hadn't stopped. The stratified sampler also spreads each pixel's
+//! samples out over how many it's going to get, so carrying on with more
+//! samples than the render started with is fine, but they won't be spread
+//! out quite as evenly.
+//!
+//! The settings the samples depend on, and a hash of the rest of the scene,
+//! are saved too, so a checkpoint can't be carried on or merged with a render
+//! of something else.
+//!
+//! Renders of different regions or ranges of samples, maybe on different
+//! machines, can be merged by adding up their sums. A merged checkpoint keeps
+//! the region and samples of each of its parts, so it can be checked that no
+//! two of them took the same samples of the same pixels, whatever order
+//! they're merged in. Only the pixels a checkpoint's samples reach are
+//! written, so a partial render of a small region makes a small file.
+
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::adaptive::Estimate;
+use crate::colour::Colour;
+use crate::scene::Scene;
+use crate::tile::Tile;
+
+const MAGIC: &str = "mobula checkpoint 3";
+
+/// The names of the settings saved in a checkpoint, in the order they're
+/// written.
+const SETTINGS: [&str; 5] = ["seed", "sampler", "filter", "depth", "scene"];
+
+/// The most pixels a checkpoint can have, so a broken one can't ask for more
+/// memory than any render would need.
+const MAX_PIXELS: u64 = 1 << 28;
+
+/// The state of a render after some number of samples per pixel.
+#[derive(Clone, Debug)]
+pub struct Checkpoint {
+    width: u32,
+    height: u32,
+    // How far outside the regions the filter can splat samples.
+    reach: u32,
+    // The renders that make up this one. There's only one unless others
+    // have been merged into it.
+    parts: Vec<Part>,
+    // The values of `SETTINGS` for the render.
+    settings: Vec<String>,
+    pub(crate) sums: Vec<(Colour, f64)>,
+    pub(crate) estimates: Vec<Estimate>,
+}
+
+/// The samples a render took of each pixel in its region.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Part {
+    region: Tile,
+    first_sample: u32,
+    samples: u32,
+    // Where the render stops, if it's only taking some of the samples.
+    end: Option<u32>,
+}
+
+impl Part {
+    /// Whether the two took any of the same samples of the same pixels.
+    fn overlaps(self, other: Part) -> bool {
+        !self.region.intersection(other.region).is_empty()
+            && self.first_sample.max(other.first_sample) < self.samples.min(other.samples)
+    }
+}
+
+impl Checkpoint {
+    /// A render of the whole image that hasn't started yet.
+    pub fn new(scene: &Scene) -> Self {
+        let config = &scene.config;
+        let pixels = (config.width * config.height) as usize;
+        Checkpoint {
+            width: config.width,
+            height: config.height,
+            reach: config.filter.radius().ceil() as u32,
+            parts: vec![Part {
+                region: Tile::image(config.width, config.height),
+                first_sample: 0,
+                samples: 0,
+                end: None,
+            }],
+            settings: settings(scene),
+            sums: vec![(Colour::black(), 0.0); pixels],
+            estimates: vec![Estimate::default(); pixels],
+        }
+    }
+
+    /// Only render the pixels in `region`.
+    pub fn with_region(mut self, region: Tile) -> Self {
+        let image = Tile::image(self.width, self.height);
+        self.part_mut().region = region.intersection(image);
+        self
+    }
+
+    /// Start from sample `first` of each pixel, rather than the first.
+    pub fn starting_at(mut self, first: u32) -> Self {
+        let part = self.part_mut();
+        part.first_sample = first;
+        part.samples = first;
+        self
+    }
+
+    /// Stop at sample `end` of each pixel, rather than going on to the
+    /// scene's number of samples. Each pixel's samples are still spread out
+    /// as if it was getting all of them, so renders of the rest can be
+    /// merged with this one.
+    pub fn ending_at(mut self, end: u32) -> Self {
+        self.part_mut().end = Some(end);
+        self
+    }
+
+    /// The render that's carried on if this one is.
+    fn part_mut(&mut self) -> &mut Part {
+        // There's always at least one part.
+        self.parts.last_mut().unwrap()
+    }
+
+    /// The width and height of the image.
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// The pixels being rendered, or the smallest tile covering all of
+    /// them for merged renders.
+    pub fn region(&self) -> Tile {
+        self.parts
+            .iter()
+            .fold(Tile::image(0, 0), |region, part| region.union(part.region))
+    }
+
+    /// The index of the first sample taken of each pixel, or the earliest
+    /// for merged renders.
+    pub fn first_sample(&self) -> u32 {
+        self.parts
+            .iter()
+            .map(|part| part.first_sample)
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// How far the render has got, as the index after the last sample taken
+    /// of each pixel, or the furthest any part has got for merged renders.
+    /// With adaptive sampling some pixels will have stopped before this.
+    pub fn samples(&self) -> u32 {
+        self.parts
+            .iter()
+            .map(|part| part.samples)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The sample the render stops at, if it's only taking some of them.
+    pub fn end(&self) -> Option<u32> {
+        self.parts.last().and_then(|part| part.end)
+    }
+
+    pub(crate) fn set_samples(&mut self, samples: u32) {
+        self.part_mut().samples = samples;
+    }
+
+    /// The pixels the samples can have been splatted into.
+    fn area(&self) -> Tile {
+        let region = self.region();
+        if region.is_empty() {
+            return region;
+        }
+        region
+            .grow(self.reach)
+            .intersection(Tile::image(self.width, self.height))
+    }
+
+    /// Whether the render can be carried on with `scene`. The image has to
+    /// be the same size, and everything that changes the samples has to be
+    /// the same, but the number of samples and how the image is shown can
+    /// change.
+    pub fn check(&self, scene: &Scene) -> Result<(), String> {
+        let config = &scene.config;
+        if (self.width, self.height) != (config.width, config.height) {
+            return Err(format!(
+                "the checkpoint is {}x{}, but the image is {}x{}",
+                self.width, self.height, config.width, config.height
+            ));
+        }
+        if self.parts.len() > 1 {
+            return Err("a checkpoint merged from several renders can't be carried on".to_string());
+        }
+        self.check_settings(scene)
+    }
+
+    /// Whether everything that changes the samples is the same in `scene`,
+    /// whatever size the image is and however many renders this was merged
+    /// from.
+    pub fn check_settings(&self, scene: &Scene) -> Result<(), String> {
+        compare_settings(&self.settings, &settings(scene))
+    }
+
+    /// Add the samples of another render of the same image. No part of one
+    /// can have taken the same samples of the same pixels as a part of the
+    /// other, or those would be counted twice.
+    pub fn merge(&mut self, other: &Checkpoint) -> Result<(), String> {
+        if (self.width, self.height) != (other.width, other.height) {
+            return Err(format!(
+                "can't merge a {}x{} render with a {}x{} one",
+                self.width, self.height, other.width, other.height
+            ));
+        }
+        compare_settings(&other.settings, &self.settings)?;
+        let overlap = self
+            .parts
+            .iter()
+            .any(|&a| other.parts.iter().any(|&b| a.overlaps(b)));
+        if overlap {
+            return Err("the renders have taken some of the same samples".to_string());
+        }
+
+        for (sum, &(colour, weight)) in self.sums.iter_mut().zip(&other.sums) {
+            *sum = (sum.0 + colour, sum.1 + weight);
+        }
+        for (estimate, &other) in self.estimates.iter_mut().zip(&other.estimates) {
+            *estimate = estimate.merge(other);
+        }
+
+        self.reach = self.reach.max(other.reach);
+        self.parts.extend_from_slice(&other.parts);
+        Ok(())
+    }
+
+    /// Write the checkpoint. It's written next to `path` first and then
+    /// moved over it, so an older checkpoint isn't lost if this is stopped
+    /// part way through.
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".partial");
+
+        let mut file = BufWriter::new(File::create(&partial)?);
+        write!(
+            file,
+            "{}\n{} {} {}\n{}\n",
+            MAGIC,
+            self.width,
+            self.height,
+            self.reach,
+            self.parts.len(),
+        )?;
+        for part in &self.parts {
+            let region = part.region;
+            write!(
+                file,
+                "{} {} {} {} {} {}",
+                region.left,
+                region.top,
+                region.right,
+                region.bottom,
+                part.first_sample,
+                part.samples,
+            )?;
+            match part.end {
+                Some(end) => writeln!(file, " {}", end)?,
+                None => writeln!(file)?,
+            }
+        }
+        for (name, value) in SETTINGS.iter().zip(&self.settings) {
+            writeln!(file, "{} {}", name, value)?;
+        }
+
+        let area = self.area();
+        for y in area.top..area.bottom {
+            for x in area.left..area.right {
+                let index = (y * self.width + x) as usize;
+                let (colour, weight) = self.sums[index];
+                for value in &[colour.r, colour.g, colour.b, weight] {
+                    file.write_all(&value.to_le_bytes())?;
+                }
+                let estimate = self.estimates[index];
+                file.write_all(&estimate.count.to_le_bytes())?;
+                file.write_all(&estimate.mean.to_le_bytes())?;
+                file.write_all(&estimate.m2.to_le_bytes())?;
+            }
+        }
+        file.into_inner()?.sync_all()?;
+
+        fs::rename(&partial, path)
+    }
+
+    pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut file = BufReader::new(File::open(path)?);
+
+        let mut line = String::new();
+        file.read_line(&mut line)?;
+        if line.trim_end() != MAGIC {
+            return Err(invalid("not a mobula checkpoint"));
+        }
+
+        let (width, height, reach) = match read_numbers(&mut file)?[..] {
+            [width, height, reach] => (width, height, reach),
+            _ => return Err(invalid("bad checkpoint size")),
+        };
+        let parts = match read_numbers(&mut file)?[..] {
+            [parts] if parts > 0 => parts,
+            _ => return Err(invalid("bad checkpoint parts")),
+        };
+        let parts = (0..parts)
+            .map(|_| match read_numbers(&mut file)?[..] {
+                [left, top, right, bottom, first_sample, samples, ref end @ ..]
+                    if end.len() <= 1 =>
+                {
+                    let region = Tile {
+                        left,
+                        top,
+                        right,
+                        bottom,
+                    };
+                    if region.right > width || region.bottom > height {
+                        return Err(invalid("the checkpoint's region is outside the image"));
+                    }
+                    Ok(Part {
+                        region,
+                        first_sample,
+                        samples,
+                        end: end.first().copied(),
+                    })
+                }
+                _ => Err(invalid("bad checkpoint part")),
+            })
+            .collect::<io::Result<Vec<Part>>>()?;
+        let settings = SETTINGS
+            .iter()
+            .map(|name| read_setting(&mut file, name))
+            .collect::<io::Result<Vec<String>>>()?;
+
+        let pixels = match u64::from(width).checked_mul(u64::from(height)) {
+            Some(pixels) if pixels <= MAX_PIXELS => pixels as usize,
+            _ => return Err(invalid("the checkpoint's image is too big")),
+        };
+        let mut checkpoint = Checkpoint {
+            width,
+            height,
+            reach,
+            parts,
+            settings,
+            sums: vec![(Colour::black(), 0.0); pixels],
+            estimates: vec![Estimate::default(); pixels],
+        };
+
+        let area = checkpoint.area();
+        for y in area.top..area.bottom {
+            for x in area.left..area.right {
+                let index = (y * width + x) as usize;
+                let r = read_f64(&mut file)?;
+                let g = read_f64(&mut file)?;
+                let b = read_f64(&mut file)?;
+                // Filters with negative lobes can leave the sums negative,
+                // which `Colour::new` doesn't allow.
+                checkpoint.sums[index] = (Colour { r, g, b }, read_f64(&mut file)?);
+
+                let mut count = [0; 4];
+                file.read_exact(&mut count)?;
+                checkpoint.estimates[index] = Estimate {
+                    count: u32::from_le_bytes(count),
+                    mean: read_f64(&mut file)?,
+                    m2: read_f64(&mut file)?,
+                };
+            }
+        }
+
+        Ok(checkpoint)
+    }
+}
+
+/// The settings the render's samples depend on, as they're written in the
+/// checkpoint. Everything in the scene that isn't a setting of its own goes
+/// into the hash, except for how many samples are taken and how the image is
+/// shown, which can change.
+fn settings(scene: &Scene) -> Vec<String> {
+    let config = &scene.config;
+    let rest = (
+        &scene.camera,
+        &scene.background,
+        scene.objects(),
+        &scene.lights,
+        config.integrator,
+        config.light_sampling,
+    );
+    let json = serde_json::to_vec(&rest).expect("scenes can always be written");
+    vec![
+        config.seed.to_string(),
+        format!("{:?}", config.sampler),
+        format!("{:?}", config.filter),
+        config.depth.to_string(),
+        format!("{:016x}", fnv1a(&json)),
+    ]
+}
+
+/// An error unless the settings a checkpoint was made with are the same as
+/// the ones it's being used with.
+fn compare_settings(saved: &[String], current: &[String]) -> Result<(), String> {
+    for ((name, saved), current) in SETTINGS.iter().zip(saved).zip(current) {
+        if saved != current {
+            return Err(if *name == "scene" {
+                "the checkpoint was rendered from a different scene".to_string()
+            } else {
+                format!(
+                    "the checkpoint was rendered with {} {}, but the scene has {}",
+                    name, saved, current
+                )
+            });
+        }
+    }
+    Ok(())
+}
+
+/// The 64-bit FNV-1a hash, which unlike the standard library's hasher is the
+/// same on every machine and in every build.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// A line of numbers separated by spaces.
+fn read_numbers<R: BufRead>(reader: &mut R) -> io::Result<Vec<u32>> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    line.split_whitespace()
+        .map(str::parse)
+        .collect::<Result<Vec<u32>, _>>()
+        .map_err(|_| invalid("bad checkpoint header"))
+}
+
+/// A line with a setting's name and its value.
+fn read_setting<R: BufRead>(reader: &mut R, name: &str) -> io::Result<String> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    match line.trim_end().split_once(' ') {
+        Some((found, value)) if found == name => Ok(value.to_string()),
+        _ => Err(invalid(&format!("the checkpoint is missing its {}", name))),
+    }
+}
+
+fn read_f64<R: Read>(reader: &mut R) -> io::Result<f64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(f64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::filter::Filter;
+
+    /// A path in the temporary directory which is removed when it's dropped.
+    struct TempPath(std::path::PathBuf);
+
+    impl TempPath {
+        fn new(name: &str) -> Self {
+            let file = format!("mobula-{}-{}.ckpt", name, std::process::id());
+            TempPath(std::env::temp_dir().join(file))
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn scene(width: u32, height: u32) -> Scene {
+        Scene::new().config(Config {
+            width,
+            height,
+            ..Config::default()
+        })
+    }
+
+    fn assert_same(a: &Checkpoint, b: &Checkpoint) {
+        assert_eq!(a.size(), b.size());
+        assert_eq!(a.region(), b.region());
+        assert_eq!(a.reach, b.reach);
+        assert_eq!(a.first_sample(), b.first_sample());
+        assert_eq!(a.samples(), b.samples());
+        assert_eq!(a.end(), b.end());
+        assert_eq!(a.settings, b.settings);
+        assert_eq!(a.sums, b.sums);
+        for (a, b) in a.estimates.iter().zip(&b.estimates) {
+            assert_eq!((a.count, a.mean, a.m2), (b.count, b.mean, b.m2));
+        }
+    }
+
+    #[test]
+    fn round_trip_keeps_negative_sums() {
+        let mut checkpoint = Checkpoint::new(&scene(4, 3)).starting_at(2).ending_at(8);
+        checkpoint.set_samples(6);
+        for (i, sum) in checkpoint.sums.iter_mut().enumerate() {
+            let x = i as f64;
+            *sum = (
+                Colour {
+                    r: -0.25 * x,
+                    g: x,
+                    b: -1.5,
+                },
+                x - 2.0,
+            );
+        }
+        for (i, estimate) in checkpoint.estimates.iter_mut().enumerate() {
+            *estimate = Estimate {
+                count: i as u32,
+                mean: 0.5 * i as f64,
+                m2: 0.125,
+            };
+        }
+
+        let path = TempPath::new("round-trip");
+        checkpoint.write(&path.0).unwrap();
+        assert_same(&Checkpoint::read(&path.0).unwrap(), &checkpoint);
+    }
+
+    /// A render of samples `first` up to `end` of the pixels in `region`,
+    /// where every sample found the same light.
+    fn rendered(region: Tile, first: u32, end: u32) -> Checkpoint {
+        let mut checkpoint = Checkpoint::new(&scene(4, 3))
+            .with_region(region)
+            .starting_at(first);
+        checkpoint.set_samples(end);
+        for y in region.top..region.bottom {
+            for x in region.left..region.right {
+                let index = (y * 4 + x) as usize;
+                let samples = f64::from(end - first);
+                checkpoint.sums[index] = (Colour::white() * samples, samples);
+                checkpoint.estimates[index] = Estimate {
+                    count: end - first,
+                    mean: 1.0,
+                    m2: 0.0,
+                };
+            }
+        }
+        checkpoint
+    }
+
+    fn merged(parts: &[Checkpoint]) -> Result<Checkpoint, String> {
+        let mut merged = parts[0].clone();
+        for part in &parts[1..] {
+            merged.merge(part)?;
+        }
+        Ok(merged)
+    }
+
+    #[test]
+    fn merge_in_any_order() {
+        let image = Tile::image(4, 3);
+        let a = rendered(image, 0, 2);
+        let b = rendered(image, 2, 4);
+        let c = rendered(image, 4, 6);
+
+        let in_order = merged(&[a.clone(), b.clone(), c.clone()]).unwrap();
+        let out_of_order = merged(&[a, c, b]).unwrap();
+        for merged in &[in_order, out_of_order] {
+            assert_eq!(merged.first_sample(), 0);
+            assert_eq!(merged.samples(), 6);
+            assert!(merged.sums.iter().all(|&(_, weight)| weight == 6.0));
+            assert!(merged.estimates.iter().all(|e| e.count == 6));
+        }
+    }
+
+    #[test]
+    fn merge_rejects_the_same_samples_twice() {
+        let image = Tile::image(4, 3);
+        let a = rendered(image, 0, 2);
+        let c = rendered(image, 4, 6);
+        let overlapping = rendered(image, 1, 5);
+
+        assert!(merged(&[a.clone(), c.clone(), overlapping.clone()]).is_err());
+        assert!(merged(&[overlapping, a.clone(), c]).is_err());
+        assert!(merged(&[a.clone(), a]).is_err());
+    }
+
+    #[test]
+    fn merge_regions() {
+        let left = Tile {
+            left: 0,
+            top: 0,
+            right: 2,
+            bottom: 3,
+        };
+        let right = Tile {
+            left: 2,
+            top: 0,
+            right: 4,
+            bottom: 3,
+        };
+        let mut merged = rendered(left, 0, 4);
+        merged.merge(&rendered(right, 0, 4)).unwrap();
+        assert_eq!(merged.region(), Tile::image(4, 3));
+        assert!(merged.estimates.iter().all(|e| e.count == 4));
+
+        // The rest of the samples of one half.
+        merged.merge(&rendered(right, 4, 8)).unwrap();
+        assert!(merged.merge(&rendered(Tile::image(4, 3), 6, 8)).is_err());
+    }
+
+    #[test]
+    fn round_trip_keeps_merged_parts() {
+        let image = Tile::image(4, 3);
+        let merged = merged(&[rendered(image, 0, 2), rendered(image, 4, 6)]).unwrap();
+
+        let path = TempPath::new("merged");
+        merged.write(&path.0).unwrap();
+        let read = Checkpoint::read(&path.0).unwrap();
+        assert_same(&read, &merged);
+        assert_eq!(read.parts, merged.parts);
+
+        let mut again = read;
+        assert!(again.merge(&rendered(image, 1, 3)).is_err());
+        assert!(again.merge(&rendered(image, 2, 4)).is_ok());
+        assert!(again.check(&scene(4, 3)).is_err());
+        assert!(again.check_settings(&scene(4, 3)).is_ok());
+
+        let mut other = scene(4, 3);
+        other.config.seed = 1;
+        assert!(again.check_settings(&other).is_err());
+    }
+
+    #[test]
+    fn check_rejects_different_settings() {
+        let checkpoint = Checkpoint::new(&scene(4, 3));
+        assert!(checkpoint.check(&scene(4, 3)).is_ok());
+        assert!(checkpoint.check(&scene(4, 4)).is_err());
+
+        let mut other = scene(4, 3);
+        other.config.samples += 8;
+        other.config.exposure = 1.0;
+        assert!(checkpoint.check(&other).is_ok());
+
+        other.config.filter = Filter::Lanczos { radius: 3.0 };
+        assert!(checkpoint.check(&other).is_err());
+
+        let mut other = scene(4, 3);
+        other.config.seed = 1;
+        assert!(checkpoint.check(&other).is_err());
+
+        let mut other = scene(4, 3);
+        other.config.depth = 2;
+        assert!(checkpoint.check(&other).is_err());
+
+        let other = scene(4, 3).push_light(crate::light::Light::point(
+            crate::point::Point::new(0.0, 1.0, 0.0),
+            Colour::white(),
+            1.0,
+        ));
+        assert!(checkpoint.check(&other).is_err());
+    }
+
+    #[test]
+    fn read_rejects_huge_images() {
+        let mut checkpoint = Checkpoint::new(&scene(4, 3)).with_region(Tile {
+            left: 0,
+            top: 0,
+            right: 0,
+            bottom: 0,
+        });
+        checkpoint.width = u32::MAX;
+        checkpoint.height = u32::MAX;
+
+        let path = TempPath::new("huge");
+        checkpoint.write(&path.0).unwrap();
+        let error = Checkpoint::read(&path.0).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -5,7 +5,11 @@ use std::ops::{Add, Mul};
 
 use crate::v3::V3;
 
-#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+/// A linear RGB colour.
+///
+/// While rendering, colours are amounts of light, which can be much brighter
+/// than white. They're only clamped to [0, 1] when written out as an image.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Colour {
     pub r: f64,
     pub g: f64,
@@ -14,9 +18,7 @@ pub struct Colour {
 
 impl Colour {
     pub fn new(r: f64, g: f64, b: f64) -> Self {
-        debug_assert!((0.0..=1.0).contains(&r));
-        debug_assert!((0.0..=1.0).contains(&g));
-        debug_assert!((0.0..=1.0).contains(&g));
+        debug_assert!(r >= 0.0 && g >= 0.0 && b >= 0.0);
         Colour { r, g, b }
     }
 
@@ -28,9 +30,23 @@ impl Colour {
         Colour::new(1.0, 1.0, 1.0)
     }
 
+    /// Limit each channel to [0, 1], the range displays can show.
+    pub fn clamp(self) -> Colour {
+        Colour {
+            r: self.r.clamp(0.0, 1.0),
+            g: self.g.clamp(0.0, 1.0),
+            b: self.b.clamp(0.0, 1.0),
+        }
+    }
+
+    /// How bright the colour looks, using the Rec. 709 weights.
+    pub fn luminance(self) -> f64 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
     pub fn linear_interpolation(start: Colour, end: Colour, t: f64) -> Colour {
         assert!(
-            t > 0.0 && t <= 1.0,
+            (0.0..=1.0).contains(&t),
             "for linear interpolation of colour, 't' must be between 0 and 1, but got {}",
             t
         );
@@ -58,14 +74,24 @@ impl Mul for Colour {
     }
 }
 
+impl Mul<f64> for Colour {
+    type Output = Colour;
+    fn mul(self, scale: f64) -> Self::Output {
+        Colour {
+            r: self.r * scale,
+            g: self.g * scale,
+            b: self.b * scale,
+        }
+    }
+}
+
 impl Add for Colour {
     type Output = Colour;
     fn add(self, other: Colour) -> Self::Output {
-        // We need to clamp between 0.0 and 1.0.
         Colour {
-            r: (self.r + other.r).max(0.0).min(1.0),
-            g: (self.g + other.g).max(0.0).min(1.0),
-            b: (self.b + other.b).max(0.0).min(1.0),
+            r: self.r + other.r,
+            g: self.g + other.g,
+            b: self.b + other.b,
         }
     }
 }
@@ -84,9 +110,10 @@ impl From<Colour> for V3 {
 
 impl From<Colour> for image::Rgb<u8> {
     fn from(val: Colour) -> Self {
-        let r = (255.0 * val.r) as u8;
-        let g = (255.0 * val.g) as u8;
-        let b = (255.0 * val.b) as u8;
+        let val = val.clamp();
+        let r = (255.0 * val.r).round() as u8;
+        let g = (255.0 * val.g).round() as u8;
+        let b = (255.0 * val.b).round() as u8;
 
         image::Rgb([r, g, b])
     }
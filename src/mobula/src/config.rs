@@ -1,15 +1,65 @@
 use serde::{Deserialize, Serialize};
 
+use crate::adaptive::Adaptive;
+use crate::aov::Aov;
+use crate::colour::Colour;
+use crate::filter::Filter;
+use crate::integrator::Integrator;
+use crate::sampler::Sampler;
+use crate::tile::TileOrder;
+use crate::tone_map::{ToneMap, Transfer};
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Config {
     #[serde(default = "Config::default_width")]
     pub width: u32,
     #[serde(default = "Config::default_height")]
     pub height: u32,
+    /// The most bounces a path can take. Most are ended well before this by
+    /// Russian roulette, so it can be generous.
     #[serde(default = "Config::default_depth")]
     pub depth: u32,
+    /// The samples per pixel, or the fewest with adaptive sampling.
     #[serde(default = "Config::default_samples")]
     pub samples: u32,
+    /// Keep sampling noisy pixels, up to a limit.
+    #[serde(default)]
+    pub adaptive: Option<Adaptive>,
+    /// Render in passes of this many samples per pixel, with a snapshot of
+    /// the image after each.
+    #[serde(default)]
+    pub progressive: Option<u32>,
+    /// Brighten or darken the image by this many stops.
+    #[serde(default)]
+    pub exposure: f64,
+    #[serde(default)]
+    pub tone_map: ToneMap,
+    #[serde(default)]
+    pub transfer: Transfer,
+    /// How samples are shared out between the pixels around them.
+    #[serde(default)]
+    pub filter: Filter,
+    /// How samples are spread out over each pixel, the lens, and each bounce.
+    #[serde(default)]
+    pub sampler: Sampler,
+    /// Renders with the same seed come out exactly the same.
+    #[serde(default)]
+    pub seed: u64,
+    /// Trace shadow rays towards the lights at each diffuse bounce, rather
+    /// than waiting for rays to hit them by chance.
+    #[serde(default = "Config::default_light_sampling")]
+    pub light_sampling: bool,
+    /// How the light arriving at the camera is worked out.
+    #[serde(default)]
+    pub integrator: Integrator,
+    /// The width and height of the tiles the image is rendered in.
+    #[serde(default = "Config::default_tile_size")]
+    pub tile_size: u32,
+    #[serde(default)]
+    pub tile_order: TileOrder,
+    /// The extra passes to render alongside the image.
+    #[serde(default)]
+    pub aovs: Vec<Aov>,
 }
 
 impl Default for Config {
@@ -19,6 +69,36 @@ impl Default for Config {
             width: 400,
             depth: Config::DEFAULT_DEPTH,
             samples: 8,
+            adaptive: None,
+            progressive: None,
+            exposure: 0.0,
+            tone_map: ToneMap::default(),
+            transfer: Transfer::default(),
+            filter: Filter::default(),
+            sampler: Sampler::default(),
+            seed: 0,
+            light_sampling: true,
+            integrator: Integrator::default(),
+            tile_size: Config::DEFAULT_TILE_SIZE,
+            tile_order: TileOrder::default(),
+            aovs: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Turn the light reaching a pixel into a colour ready to be written to an
+    /// image.
+    pub fn display_colour(&self, colour: Colour) -> Colour {
+        let exposed = colour * 2.0_f64.powf(self.exposure);
+        self.transfer.encode(self.tone_map.apply(exposed))
+    }
+
+    /// The most samples any pixel can get.
+    pub fn max_samples(&self) -> u32 {
+        match self.adaptive {
+            Some(adaptive) => adaptive.max_samples.max(self.samples),
+            None => self.samples,
         }
     }
 }
@@ -27,8 +107,9 @@ impl Default for Config {
 impl Config {
     const DEFAULT_HEIGHT: u32 = 300;
     const DEFAULT_WIDTH: u32 = 400;
-    const DEFAULT_DEPTH: u32 = 8;
+    const DEFAULT_DEPTH: u32 = 32;
     const DEFAULT_SAMPLES: u32 = 8;
+    const DEFAULT_TILE_SIZE: u32 = 32;
 
     fn default_height() -> u32 {
         Config::DEFAULT_HEIGHT
@@ -45,4 +126,12 @@ impl Config {
     fn default_samples() -> u32 {
         Config::DEFAULT_SAMPLES
     }
+
+    fn default_tile_size() -> u32 {
+        Config::DEFAULT_TILE_SIZE
+    }
+
+    fn default_light_sampling() -> bool {
+        true
+    }
 }
@@ -0,0 +1,137 @@
+//! Piecewise-constant probability distributions, for picking things like
+//! pixels of an environment map in proportion to how bright they are.
+
+/// A distribution over [0, 1) made of `n` equal-width steps, each as likely as
+/// its weight.
+#[derive(Clone, Debug)]
+pub struct Distribution1D {
+    weights: Vec<f64>,
+    cdf: Vec<f64>,
+    total: f64,
+}
+
+impl Distribution1D {
+    pub fn new(weights: Vec<f64>) -> Self {
+        // With nothing to go on, every step is equally likely.
+        let weights = if weights.iter().sum::<f64>() > 0.0 {
+            weights
+        } else {
+            vec![1.0; weights.len()]
+        };
+
+        let mut cdf = Vec::with_capacity(weights.len() + 1);
+        let mut total = 0.0;
+        cdf.push(total);
+        for w in &weights {
+            total += w;
+            cdf.push(total);
+        }
+
+        Distribution1D {
+            weights,
+            cdf,
+            total,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.weights.len()
+    }
+
+    /// Map a uniform sample in [0, 1) to a step. Returns the step's index,
+    /// the position of the sample in [0, 1), and the density at that position.
+    pub fn sample(&self, u: f64) -> (usize, f64, f64) {
+        let target = u * self.total;
+        // The last step whose start is at or before the target, skipping any
+        // empty steps.
+        let i = (self.cdf.partition_point(|&c| c <= target).max(1) - 1).min(self.len() - 1);
+        let offset = if self.weights[i] > 0.0 {
+            (target - self.cdf[i]) / self.weights[i]
+        } else {
+            0.0
+        };
+        let x = ((i as f64 + offset) / self.len() as f64).min(1.0 - f64::EPSILON);
+        (i, x, self.density(i))
+    }
+
+    /// The probability of `sample` landing in step `i`.
+    pub fn probability(&self, i: usize) -> f64 {
+        self.weights[i] / self.total
+    }
+
+    /// The density of `sample`'s positions in step `i`.
+    pub fn density(&self, i: usize) -> f64 {
+        self.probability(i) * self.len() as f64
+    }
+}
+
+/// A distribution over [0, 1)², made of a grid of steps.
+#[derive(Clone, Debug)]
+pub struct Distribution2D {
+    rows: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    /// Weights are in rows, with `width` weights in each.
+    pub fn new(weights: &[f64], width: usize) -> Self {
+        let rows: Vec<Distribution1D> = weights
+            .chunks(width)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        // From the weights themselves, as a row with none is spread out
+        // evenly but shouldn't be picked.
+        let marginal =
+            Distribution1D::new(weights.chunks(width).map(|row| row.iter().sum()).collect());
+        Distribution2D { rows, marginal }
+    }
+
+    /// Map a uniform sample in [0, 1)² to a position in [0, 1)², returning the
+    /// position and the density there.
+    pub fn sample(&self, u: (f64, f64)) -> ((f64, f64), f64) {
+        let (row, y, row_density) = self.marginal.sample(u.1);
+        let (_, x, column_density) = self.rows[row].sample(u.0);
+        ((x, y), row_density * column_density)
+    }
+
+    /// The density of `sample`'s positions at `(x, y)`.
+    pub fn density(&self, (x, y): (f64, f64)) -> f64 {
+        let row = ((y * self.marginal.len() as f64) as usize).min(self.marginal.len() - 1);
+        let distribution = &self.rows[row];
+        let column = ((x * distribution.len() as f64) as usize).min(distribution.len() - 1);
+        self.marginal.density(row) * distribution.density(column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_rows_are_never_picked() {
+        // The top half lit, and the bottom half black.
+        let weights: Vec<f64> = (0..8 * 4)
+            .map(|i| if i < 8 * 2 { 0.5 } else { 0.0 })
+            .collect();
+        let distribution = Distribution2D::new(&weights, 8);
+
+        let n = 16;
+        for i in 0..n {
+            for j in 0..n {
+                let u = ((i as f64 + 0.5) / n as f64, (j as f64 + 0.5) / n as f64);
+                let ((x, y), density) = distribution.sample(u);
+                assert!(y < 0.5, "picked ({}, {}) in a black row", x, y);
+                assert!((density - 2.0).abs() < 1e-9);
+                assert!((distribution.density((x, y)) - density).abs() < 1e-9);
+            }
+        }
+        assert_eq!(distribution.density((0.5, 0.75)), 0.0);
+    }
+
+    #[test]
+    fn empty_distributions_are_uniform() {
+        let distribution = Distribution1D::new(vec![0.0; 4]);
+        assert_eq!(distribution.probability(2), 0.25);
+        assert_eq!(distribution.sample(0.6).0, 2);
+    }
+}
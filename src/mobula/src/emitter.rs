@@ -0,0 +1,400 @@
+//! The objects in a scene which give off light, so shadow rays can be aimed
+//! straight at them.
+//!
+//! These are the shapes with emissive materials. The analytic lights in
+//! `light` are sampled separately.
+//!
+//! Small lights are hard to find by bouncing rays around at random. Instead,
+//! at each diffuse hit a point on a light is picked and a shadow ray traced to
+//! it. Lights are picked in proportion to how much light they give off, and
+//! triangles in a mesh in proportion to their share of it.
+
+use std::f64::consts::PI;
+
+use crate::distribution::Distribution1D;
+use crate::hit::Hit;
+use crate::material::{Material, Scatter};
+use crate::point::Point;
+use crate::ray::Ray;
+use crate::shape::sphere::Sphere;
+use crate::shape::triangle::Triangle;
+use crate::shape::Shape;
+use crate::v3::V3;
+
+/// A point picked on a light.
+pub struct EmitterSample {
+    /// The direction to the point, as a unit vector.
+    pub direction: V3,
+    /// How far away the point is.
+    pub distance: f64,
+    /// The point, as if the shadow ray had hit it.
+    pub hit: Hit,
+    /// The probability density of picking the direction, by solid angle.
+    pub pdf: f64,
+}
+
+enum Emitter {
+    Sphere(Sphere),
+    /// The emissive triangles of a triangle, mesh or model, picked in
+    /// proportion to the light they give off, which adds up to `power`.
+    Triangles {
+        triangles: Vec<Triangle>,
+        choice: Distribution1D,
+        power: f64,
+    },
+}
+
+/// All the objects in a scene which give off light.
+pub struct Emitters {
+    emitters: Vec<Emitter>,
+    choice: Option<Distribution1D>,
+    // The light each object in the scene is, if it is one.
+    light_of: Vec<Option<usize>>,
+}
+
+impl Emitters {
+    pub fn new(objects: &[Shape]) -> Self {
+        let mut emitters = Vec::new();
+        let mut powers = Vec::new();
+        let mut light_of = Vec::with_capacity(objects.len());
+
+        for object in objects {
+            let light = match object {
+                Shape::Sphere(sphere) => {
+                    let area = 4.0 * PI * sphere.radius * sphere.radius;
+                    let power = area * radiance(sphere.material);
+                    if power > 0.0 {
+                        Some((Emitter::Sphere(*sphere), power))
+                    } else {
+                        None
+                    }
+                }
+                Shape::Triangle(triangle) => Emitter::triangles(vec![*triangle]),
+                Shape::Mesh(mesh) => Emitter::triangles(mesh.triangles().collect()),
+                Shape::Obj(obj) => {
+                    Emitter::triangles(obj.meshes().iter().flat_map(|m| m.triangles()).collect())
+                }
+            };
+
+            match light {
+                Some((light, power)) => {
+                    light_of.push(Some(emitters.len()));
+                    emitters.push(light);
+                    powers.push(power);
+                }
+                None => light_of.push(None),
+            }
+        }
+
+        Emitters {
+            choice: if emitters.is_empty() {
+                None
+            } else {
+                Some(Distribution1D::new(powers))
+            },
+            emitters,
+            light_of,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.emitters.is_empty()
+    }
+
+    /// Pick a point on one of the lights to light `from`, using `u` to pick
+    /// the light and `v` to pick the point on it.
+    pub fn sample(&self, from: Point, u: f64, v: (f64, f64)) -> Option<EmitterSample> {
+        let choice = self.choice.as_ref()?;
+        let (i, x, _) = choice.sample(u);
+        let probability = choice.probability(i);
+        // What's left of `u` once the light is picked.
+        let u = (x * choice.len() as f64 - i as f64).clamp(0.0, 1.0 - f64::EPSILON);
+
+        let sample = match &self.emitters[i] {
+            Emitter::Sphere(sphere) => sample_sphere(sphere, from, v),
+            Emitter::Triangles {
+                triangles,
+                choice,
+                power,
+            } => {
+                let (j, _, _) = choice.sample(u);
+                let triangle = &triangles[j];
+                let (a, b) = (v.0.sqrt(), v.1);
+                let point = triangle
+                    .a
+                    .translate((triangle.b - triangle.a) * (a * (1.0 - b)))
+                    .translate((triangle.c - triangle.a) * (a * b));
+                let normal = Triangle::normal(triangle.a, triangle.b, triangle.c);
+                let to_light = point - from;
+                let distance = to_light.magnitude();
+                let direction = to_light * (1.0 / distance);
+                let hit = Hit::new(point, normal, triangle.material, distance);
+                let area_pdf = radiance(triangle.material) / power;
+                EmitterSample {
+                    direction,
+                    distance,
+                    hit,
+                    pdf: solid_angle_pdf(area_pdf, distance, normal.dot(direction)),
+                }
+            }
+        };
+
+        if sample.pdf > 0.0 && sample.pdf.is_finite() {
+            Some(EmitterSample {
+                pdf: sample.pdf * probability,
+                ..sample
+            })
+        } else {
+            None
+        }
+    }
+
+    /// The probability density of `sample` picking the direction of `ray`,
+    /// which went on to hit a light at `hit`.
+    pub fn pdf(&self, ray: &Ray, hit: &Hit) -> f64 {
+        let (choice, i) = match (&self.choice, self.light_of.get(hit.object)) {
+            (Some(choice), Some(&Some(i))) => (choice, i),
+            _ => return 0.0,
+        };
+        let from = ray.origin();
+        let to_light = hit.intersection - from;
+        let distance = to_light.magnitude();
+        let direction = to_light * (1.0 / distance);
+
+        let pdf = match &self.emitters[i] {
+            // The same normals as `sample` uses, not ones bent by smooth
+            // shading.
+            Emitter::Sphere(sphere) => {
+                sphere_pdf(sphere, from, hit.geometric_normal.dot(direction), distance)
+            }
+            Emitter::Triangles { power, .. } => {
+                let area_pdf = radiance(hit.material) / power;
+                solid_angle_pdf(area_pdf, distance, hit.geometric_normal.dot(direction))
+            }
+        };
+
+        if pdf.is_finite() {
+            pdf * choice.probability(i)
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Emitter {
+    /// The light made of the triangles which give off light, and how much
+    /// they give off all together, unless none of them do.
+    fn triangles(triangles: Vec<Triangle>) -> Option<(Emitter, f64)> {
+        let triangles: Vec<Triangle> = triangles
+            .into_iter()
+            .filter(|t| radiance(t.material) > 0.0 && area(t) > 0.0)
+            .collect();
+        let powers: Vec<f64> = triangles
+            .iter()
+            .map(|t| area(t) * radiance(t.material))
+            .collect();
+        let power = powers.iter().sum();
+        if triangles.is_empty() {
+            return None;
+        }
+
+        let light = Emitter::Triangles {
+            triangles,
+            choice: Distribution1D::new(powers),
+            power,
+        };
+        Some((light, power))
+    }
+}
+
+/// How bright a material is, if it gives off light. This assumes it gives off
+/// the same light everywhere, as `DiffuseLight` does.
+fn radiance(material: Material) -> f64 {
+    let hit = Hit::new(Point::origin(), V3::zero(), material, 0.0);
+    material.emitted(&Ray::default(), &hit).luminance()
+}
+
+fn area(triangle: &Triangle) -> f64 {
+    (triangle.b - triangle.a)
+        .cross(triangle.c - triangle.a)
+        .magnitude()
+        / 2.0
+}
+
+/// Turn a density over a light's area into one over the directions seen from
+/// `distance` away, where the light's normal and the direction to it have a
+/// cosine of `cosine`.
+fn solid_angle_pdf(area_pdf: f64, distance: f64, cosine: f64) -> f64 {
+    area_pdf * distance * distance / cosine.abs()
+}
+
+/// Pick a point on a sphere. From outside it, only directions in the cone
+/// the sphere covers are picked, so no shadow rays are wasted on the far side
+/// of it.
+fn sample_sphere(sphere: &Sphere, from: Point, (u, v): (f64, f64)) -> EmitterSample {
+    let to_centre = sphere.centre - from;
+    let centre_distance = to_centre.magnitude();
+    let radius = sphere.radius;
+
+    if centre_distance <= radius {
+        // From inside, any point can be seen, so pick them all evenly.
+        let z = 1.0 - 2.0 * u;
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * PI * v;
+        let normal = V3::new(r * phi.cos(), r * phi.sin(), z);
+        let point = sphere.centre.translate(normal * radius);
+        let to_light = point - from;
+        let distance = to_light.magnitude();
+        let direction = to_light * (1.0 / distance);
+        let area_pdf = 1.0 / (4.0 * PI * radius * radius);
+        return EmitterSample {
+            direction,
+            distance,
+            hit: Hit::new(point, normal, sphere.material, distance),
+            pdf: solid_angle_pdf(area_pdf, distance, normal.dot(direction)),
+        };
+    }
+
+    let axis = to_centre * (1.0 / centre_distance);
+    let sin2_max = (radius / centre_distance).powi(2);
+    let cos_max = (1.0 - sin2_max).max(0.0).sqrt();
+    let cos_theta = 1.0 - u + u * cos_max;
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * v;
+
+    let (s, t) = axis.orthonormal_basis();
+    let direction = s * (sin_theta * phi.cos()) + t * (sin_theta * phi.sin()) + axis * cos_theta;
+
+    // Where the direction first meets the sphere.
+    let distance = centre_distance * cos_theta
+        - (radius * radius - (centre_distance * sin_theta).powi(2))
+            .max(0.0)
+            .sqrt();
+    let point = from.translate(direction * distance);
+    let normal = (point - sphere.centre) * (1.0 / radius);
+
+    EmitterSample {
+        direction,
+        distance,
+        hit: Hit::new(point, normal, sphere.material, distance),
+        pdf: cone_pdf(sin2_max, cos_max),
+    }
+}
+
+fn sphere_pdf(sphere: &Sphere, from: Point, cosine: f64, distance: f64) -> f64 {
+    let centre_distance = (sphere.centre - from).magnitude();
+    let radius = sphere.radius;
+    if centre_distance <= radius {
+        let area_pdf = 1.0 / (4.0 * PI * radius * radius);
+        solid_angle_pdf(area_pdf, distance, cosine)
+    } else {
+        let sin2_max = (radius / centre_distance).powi(2);
+        let cos_max = (1.0 - sin2_max).max(0.0).sqrt();
+        cone_pdf(sin2_max, cos_max)
+    }
+}
+
+/// The density of directions picked evenly from a cone. The cone's solid
+/// angle is 2π(1 - cos), written so it doesn't round to nothing for the
+/// narrow cones of small or far away lights.
+fn cone_pdf(sin2_max: f64, cos_max: f64) -> f64 {
+    (1.0 + cos_max) / (2.0 * PI * sin2_max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::background::Background;
+    use crate::camera::CameraBuilder;
+    use crate::config::Config;
+    use crate::hit::Hitable;
+    use crate::scene::Scene;
+    use crate::shape::mesh::Mesh;
+
+    /// A square light facing down, with normals bent well away from its
+    /// surface's so it's shaded as if it was curved.
+    fn smooth_light() -> Mesh {
+        let positions = vec![
+            Point::new(-1.0, 2.0, -1.0),
+            Point::new(1.0, 2.0, -1.0),
+            Point::new(1.0, 2.0, 1.0),
+            Point::new(-1.0, 2.0, 1.0),
+        ];
+        let normals = vec![
+            V3::new(-0.8, -0.6, 0.0),
+            V3::new(0.8, -0.6, 0.0),
+            V3::new(0.8, -0.6, 0.0),
+            V3::new(-0.8, -0.6, 0.0),
+        ];
+        let faces = vec![[0, 1, 2], [0, 2, 3]];
+        let material = Material::diffuse_light(1.0, 1.0, 1.0, 4.0);
+        Mesh::new(positions, normals, Vec::new(), faces, material).unwrap()
+    }
+
+    #[test]
+    fn pdf_matches_sample_on_smooth_meshes() {
+        let mesh = smooth_light();
+        let emitters = Emitters::new(&[Shape::Mesh(mesh.clone())]);
+        let from = Point::new(0.3, 0.0, -0.2);
+
+        let n = 8;
+        for i in 0..n {
+            for j in 0..n {
+                let u = (f64::from(i) + 0.5) / f64::from(n);
+                let v = (u, (f64::from(j) + 0.5) / f64::from(n));
+                let sample = emitters.sample(from, u, v).unwrap();
+
+                let ray = Ray::new(from, sample.direction);
+                let hit = mesh.is_hit_by(&ray, 0.001, f64::MAX).unwrap();
+                let pdf = emitters.pdf(&ray, &hit);
+                assert!(
+                    (pdf - sample.pdf).abs() <= 1e-9 * sample.pdf,
+                    "sampled with density {}, but pdf gives {}",
+                    sample.pdf,
+                    pdf
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn light_sampling_agrees_with_bsdf_sampling() {
+        let render = |light_sampling| {
+            let config = Config {
+                width: 12,
+                height: 12,
+                samples: 1024,
+                depth: 2,
+                light_sampling,
+                ..Config::default()
+            };
+            let camera = CameraBuilder::new()
+                .origin(Point::new(0.0, 1.0, 3.0))
+                .target(Point::new(0.0, 0.0, 0.0))
+                .fov(60.0)
+                .aperture(0.0);
+            let image = Scene::new()
+                .config(config)
+                .camera(camera)
+                .background(Background::None)
+                .push(Shape::Sphere(Sphere::new(
+                    Point::new(0.0, -1000.0, 0.0),
+                    1000.0,
+                    Material::lambertian(0.8, 0.8, 0.8),
+                )))
+                .push(Shape::Mesh(smooth_light()))
+                .render_par();
+            let pixels = image.pixels();
+            pixels.iter().map(|c| c.luminance()).sum::<f64>() / pixels.len() as f64
+        };
+
+        let with = render(true);
+        let without = render(false);
+        assert!(
+            (with - without).abs() < 0.01 * without,
+            "{} with light sampling, {} without",
+            with,
+            without
+        );
+    }
+}
@@ -0,0 +1,214 @@
+//! Image based lighting from an equirectangular environment map.
+
+use serde::{Deserialize, Serialize};
+
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::colour::Colour;
+use crate::distribution::Distribution2D;
+use crate::v3::V3;
+
+use std::f64::consts::PI;
+
+/// An environment map, loaded from a Radiance `.hdr` or OpenEXR `.exr` file
+/// in the equirectangular (latitude-longitude) layout, with +y up.
+///
+/// The middle of the image is in the -z direction, and `rotation` turns the
+/// map about the y axis by that many degrees.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "EnvironmentDescription", into = "EnvironmentDescription")]
+pub struct Environment {
+    description: EnvironmentDescription,
+    map: Arc<Map>,
+}
+
+/// How environment maps are written in scene files.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct EnvironmentDescription {
+    path: PathBuf,
+    #[serde(default)]
+    rotation: f64,
+    #[serde(default = "EnvironmentDescription::default_intensity")]
+    intensity: f64,
+}
+
+impl EnvironmentDescription {
+    fn default_intensity() -> f64 {
+        1.0
+    }
+}
+
+#[derive(Debug)]
+struct Map {
+    width: usize,
+    height: usize,
+    pixels: Vec<Colour>,
+    // For picking directions in proportion to how much light comes from them.
+    distribution: Distribution2D,
+}
+
+impl Environment {
+    pub fn load<P: AsRef<Path>>(path: P, rotation: f64, intensity: f64) -> Result<Self, String> {
+        Environment::try_from(EnvironmentDescription {
+            path: path.as_ref().to_owned(),
+            rotation,
+            intensity,
+        })
+    }
+
+    /// The light coming from a direction.
+    pub fn radiance(&self, direction: V3) -> Colour {
+        let (u, v) = self.direction_to_uv(direction);
+        self.map.pixel(u, v) * self.description.intensity
+    }
+
+    /// Pick a direction, preferring the brighter parts of the map. Returns the
+    /// direction, the light from it, and the probability density (per unit
+    /// solid angle) of picking it.
+    pub fn sample(&self, u: (f64, f64)) -> Option<(V3, Colour, f64)> {
+        let ((s, t), density) = self.map.distribution.sample(u);
+        let sin_theta = (t * PI).sin();
+        if sin_theta <= 0.0 || density <= 0.0 {
+            return None;
+        }
+        let direction = self.uv_to_direction(s, t);
+        let pdf = density / (2.0 * PI * PI * sin_theta);
+        Some((direction, self.radiance(direction), pdf))
+    }
+
+    /// The density `sample` picks a direction with.
+    pub fn pdf(&self, direction: V3) -> f64 {
+        let (u, v) = self.direction_to_uv(direction);
+        let sin_theta = (v * PI).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        self.map.distribution.density((u, v)) / (2.0 * PI * PI * sin_theta)
+    }
+
+    fn direction_to_uv(&self, direction: V3) -> (f64, f64) {
+        let d = direction.normalize();
+        let theta = d.y.clamp(-1.0, 1.0).acos();
+        let phi = d.x.atan2(-d.z) - self.description.rotation.to_radians();
+        let u = (0.5 + phi / (2.0 * PI)).rem_euclid(1.0);
+        (u, theta / PI)
+    }
+
+    fn uv_to_direction(&self, u: f64, v: f64) -> V3 {
+        let phi = (u - 0.5) * 2.0 * PI + self.description.rotation.to_radians();
+        let theta = v * PI;
+        V3::new(
+            theta.sin() * phi.sin(),
+            theta.cos(),
+            -theta.sin() * phi.cos(),
+        )
+    }
+}
+
+impl Map {
+    fn new(width: usize, height: usize, pixels: Vec<Colour>) -> Self {
+        // Rows near the poles cover less of the sphere, so they're less likely
+        // to be picked.
+        let weights: Vec<f64> = pixels
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let theta = PI * ((i / width) as f64 + 0.5) / height as f64;
+                p.luminance() * theta.sin()
+            })
+            .collect();
+
+        Map {
+            width,
+            height,
+            distribution: Distribution2D::new(&weights, width),
+            pixels,
+        }
+    }
+
+    fn pixel(&self, u: f64, v: f64) -> Colour {
+        let x = ((u * self.width as f64) as usize).min(self.width - 1);
+        let y = ((v * self.height as f64) as usize).min(self.height - 1);
+        self.pixels[y * self.width + x]
+    }
+}
+
+/// Convert a pixel from a file, where negative values are sometimes left over
+/// from filtering.
+fn colour(r: f32, g: f32, b: f32) -> Colour {
+    Colour::new(
+        (r as f64).max(0.0),
+        (g as f64).max(0.0),
+        (b as f64).max(0.0),
+    )
+}
+
+fn load_hdr(path: &Path) -> Result<Map, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let decoder = image::hdr::HDRDecoder::new(BufReader::new(file)).map_err(|e| e.to_string())?;
+    let metadata = decoder.metadata();
+    let pixels = decoder
+        .read_image_hdr()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|p| colour(p.data[0], p.data[1], p.data[2]))
+        .collect();
+    Ok(Map::new(
+        metadata.width as usize,
+        metadata.height as usize,
+        pixels,
+    ))
+}
+
+fn load_exr(path: &Path) -> Result<Map, String> {
+    let image = exr::prelude::read_first_rgba_layer_from_file(
+        path,
+        |resolution, _| (resolution.width(), vec![Colour::black(); resolution.area()]),
+        |(width, pixels), position, (r, g, b, _): (f32, f32, f32, f32)| {
+            pixels[position.y() * *width + position.x()] = colour(r, g, b);
+        },
+    )
+    .map_err(|e| e.to_string())?;
+
+    let (width, pixels) = image.layer_data.channel_data.pixels;
+    let height = pixels.len() / width.max(1);
+    Ok(Map::new(width, height, pixels))
+}
+
+impl TryFrom<EnvironmentDescription> for Environment {
+    type Error = String;
+
+    fn try_from(description: EnvironmentDescription) -> Result<Self, Self::Error> {
+        let path = &description.path;
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_lowercase);
+
+        let map = match extension.as_deref() {
+            Some("hdr") => load_hdr(path),
+            Some("exr") => load_exr(path),
+            _ => Err("environment maps must be .hdr or .exr files".into()),
+        }
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        if map.pixels.is_empty() {
+            return Err(format!("{}: the image is empty", path.display()));
+        }
+
+        Ok(Environment {
+            description,
+            map: Arc::new(map),
+        })
+    }
+}
+
+impl From<Environment> for EnvironmentDescription {
+    fn from(environment: Environment) -> Self {
+        environment.description
+    }
+}
@@ -0,0 +1,236 @@
+//! Reconstruction filters, which decide how much each sample counts towards
+//! the pixels around it.
+//!
+//! Each sample is splatted into every pixel whose centre is within the
+//! filter's radius, weighted by the filter, and each pixel is the weighted
+//! average of the samples it gets.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use std::f64::consts::PI;
+use std::str::FromStr;
+
+use crate::named::{self, Named};
+
+/// A separable filter, with its radius in pixels.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(remote = "Self", tag = "type")]
+#[serde(rename_all = "snake_case")]
+pub enum Filter {
+    /// Every sample within the radius counts the same. With the default
+    /// radius a sample only counts towards the pixel it's in.
+    Box {
+        #[serde(default = "Filter::default_box_radius")]
+        radius: f64,
+    },
+    /// Samples count less the further they are from the pixel's centre.
+    Tent {
+        #[serde(default = "Filter::default_tent_radius")]
+        radius: f64,
+    },
+    /// A Gaussian bump, shifted down so it's 0 at the radius.
+    Gaussian {
+        #[serde(default = "Filter::default_gaussian_radius")]
+        radius: f64,
+        #[serde(default = "Filter::default_sigma")]
+        sigma: f64,
+    },
+    /// The Mitchell-Netravali cubic, which trades off blurring against
+    /// ringing with `b` and `c`.
+    Mitchell {
+        #[serde(default = "Filter::default_mitchell_radius")]
+        radius: f64,
+        #[serde(default = "Filter::default_b_and_c")]
+        b: f64,
+        #[serde(default = "Filter::default_b_and_c")]
+        c: f64,
+    },
+    /// A sinc windowed by a wider sinc, which keeps edges sharp.
+    Lanczos {
+        #[serde(default = "Filter::default_lanczos_radius")]
+        radius: f64,
+    },
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter::Box {
+            radius: Filter::default_box_radius(),
+        }
+    }
+}
+
+impl Filter {
+    pub const NAMES: [&'static str; 5] = ["box", "tent", "gaussian", "mitchell", "lanczos"];
+
+    pub fn radius(self) -> f64 {
+        match self {
+            Filter::Box { radius }
+            | Filter::Tent { radius }
+            | Filter::Gaussian { radius, .. }
+            | Filter::Mitchell { radius, .. }
+            | Filter::Lanczos { radius } => radius,
+        }
+    }
+
+    /// The same filter, with a different radius.
+    pub fn with_radius(mut self, new_radius: f64) -> Self {
+        match &mut self {
+            Filter::Box { radius }
+            | Filter::Tent { radius }
+            | Filter::Gaussian { radius, .. }
+            | Filter::Mitchell { radius, .. }
+            | Filter::Lanczos { radius } => *radius = new_radius,
+        }
+        self
+    }
+
+    /// How much a sample at (`x`, `y`) pixels from a pixel's centre counts
+    /// towards it. This can be negative.
+    pub fn weight(self, x: f64, y: f64) -> f64 {
+        self.weight_1d(x) * self.weight_1d(y)
+    }
+
+    fn weight_1d(self, x: f64) -> f64 {
+        let x = x.abs();
+        match self {
+            Filter::Box { .. } => 1.0,
+            Filter::Tent { radius } => (radius - x).max(0.0),
+            Filter::Gaussian { radius, sigma } => {
+                let gaussian = |x: f64| (-x * x / (2.0 * sigma * sigma)).exp();
+                (gaussian(x) - gaussian(radius)).max(0.0)
+            }
+            Filter::Mitchell { radius, b, c } => {
+                // The cubic is defined over [-2, 2].
+                let x = (2.0 * x / radius).min(2.0);
+                let polynomial = if x > 1.0 {
+                    (-b - 6.0 * c) * x.powi(3)
+                        + (6.0 * b + 30.0 * c) * x * x
+                        + (-12.0 * b - 48.0 * c) * x
+                        + (8.0 * b + 24.0 * c)
+                } else {
+                    (12.0 - 9.0 * b - 6.0 * c) * x.powi(3)
+                        + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                        + (6.0 - 2.0 * b)
+                };
+                polynomial / 6.0
+            }
+            Filter::Lanczos { radius } if x < radius => sinc(x) * sinc(x / radius),
+            Filter::Lanczos { .. } => 0.0,
+        }
+    }
+
+    fn default_box_radius() -> f64 {
+        0.5
+    }
+
+    fn default_tent_radius() -> f64 {
+        1.0
+    }
+
+    fn default_gaussian_radius() -> f64 {
+        1.5
+    }
+
+    fn default_sigma() -> f64 {
+        0.5
+    }
+
+    fn default_mitchell_radius() -> f64 {
+        2.0
+    }
+
+    fn default_b_and_c() -> f64 {
+        1.0 / 3.0
+    }
+
+    fn default_lanczos_radius() -> f64 {
+        3.0
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "box" => Ok(Filter::default()),
+            "tent" => Ok(Filter::Tent {
+                radius: Filter::default_tent_radius(),
+            }),
+            "gaussian" => Ok(Filter::Gaussian {
+                radius: Filter::default_gaussian_radius(),
+                sigma: Filter::default_sigma(),
+            }),
+            "mitchell" => Ok(Filter::Mitchell {
+                radius: Filter::default_mitchell_radius(),
+                b: Filter::default_b_and_c(),
+                c: Filter::default_b_and_c(),
+            }),
+            "lanczos" => Ok(Filter::Lanczos {
+                radius: Filter::default_lanczos_radius(),
+            }),
+            _ => Err(format!("unknown filter '{}'", s)),
+        }
+    }
+}
+
+impl Named for Filter {
+    const WHAT: &'static str = "filter";
+    const NAMES: &'static [&'static str] = &Filter::NAMES;
+
+    fn read_tagged<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Filter::deserialize(deserializer)
+    }
+}
+
+impl Serialize for Filter {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Filter::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Filter {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        named::read(deserializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(json: &str) -> Result<Filter, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    #[test]
+    fn reads_names_and_objects() {
+        assert_eq!(read(r#""tent""#).unwrap(), "tent".parse().unwrap());
+        assert_eq!(read(r#""box""#).unwrap(), Filter::default());
+        assert_eq!(
+            read(r#"{"type": "gaussian", "sigma": 0.3}"#).unwrap(),
+            Filter::Gaussian {
+                radius: Filter::default_gaussian_radius(),
+                sigma: 0.3
+            }
+        );
+        assert!(read(r#""tnet""#).is_err());
+        assert!(read(r#"{"type": "tnet"}"#).is_err());
+    }
+
+    #[test]
+    fn round_trip() {
+        let filter = Filter::Lanczos { radius: 2.5 };
+        let json = serde_json::to_string(&filter).unwrap();
+        assert_eq!(read(&json).unwrap(), filter);
+    }
+}
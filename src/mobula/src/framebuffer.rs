@@ -0,0 +1,197 @@
+//! The rendered image, kept as linear floating-point colours.
+
+use exr::prelude::{
+    AnyChannel, AnyChannels, Encoding, FlatSamples, Image, Layer, LayerAttributes, SmallVec,
+    WritableImage,
+};
+use image::{ImageBuffer, Rgb};
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::aov::Aov;
+use crate::colour::Colour;
+use crate::config::Config;
+
+/// A rendered image, stored in rows from the top left.
+///
+/// The colours are the light that reached the camera, before any exposure,
+/// tone mapping or encoding, which is what compositors want. Any AOVs are kept
+/// alongside it in the same layout.
+#[derive(Clone, Debug)]
+pub struct Framebuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<Colour>,
+    aovs: Vec<(Aov, Vec<Colour>)>,
+    // How many samples each pixel got, if it's been rendered.
+    sample_counts: Vec<u32>,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32, pixels: Vec<Colour>) -> Self {
+        Framebuffer::check_size(width, height, &pixels);
+        Framebuffer {
+            width,
+            height,
+            pixels,
+            aovs: Vec::new(),
+            sample_counts: Vec::new(),
+        }
+    }
+
+    /// Record how many samples each pixel got.
+    pub fn with_sample_counts(mut self, sample_counts: Vec<u32>) -> Self {
+        assert_eq!(sample_counts.len(), self.pixels.len());
+        self.sample_counts = sample_counts;
+        self
+    }
+
+    /// Add a pass, replacing any earlier one for the same AOV.
+    pub fn with_aov(mut self, aov: Aov, pixels: Vec<Colour>) -> Self {
+        Framebuffer::check_size(self.width, self.height, &pixels);
+        self.aovs.retain(|(a, _)| *a != aov);
+        self.aovs.push((aov, pixels));
+        self
+    }
+
+    fn check_size(width: u32, height: u32, pixels: &[Colour]) {
+        assert_eq!(
+            pixels.len(),
+            (width * height) as usize,
+            "a {}x{} framebuffer needs {} pixels, but got {}",
+            width,
+            height,
+            width * height,
+            pixels.len()
+        );
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[Colour] {
+        &self.pixels
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> Colour {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    /// How many samples each pixel got, in the same order as the pixels. This
+    /// is empty if the framebuffer didn't come from a render.
+    pub fn sample_counts(&self) -> &[u32] {
+        &self.sample_counts
+    }
+
+    /// The AOVs rendered, in the order they were added.
+    pub fn aovs(&self) -> impl Iterator<Item = Aov> + '_ {
+        self.aovs.iter().map(|(aov, _)| *aov)
+    }
+
+    /// A pass on its own, so it can be written to a file like any image.
+    pub fn aov(&self, aov: Aov) -> Option<Framebuffer> {
+        let (_, pixels) = self.aovs.iter().find(|(a, _)| *a == aov)?;
+        Some(Framebuffer::new(self.width, self.height, pixels.clone()))
+    }
+
+    /// An 8-bit image for display, using the exposure, tone mapping and
+    /// transfer function in `config`.
+    pub fn to_image(&self, config: &Config) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        ImageBuffer::from_fn(self.width, self.height, |x, y| {
+            config.display_colour(self.pixel(x, y)).into()
+        })
+    }
+
+    /// An 8-bit image of a pass, made to be looked at rather than used.
+    pub fn aov_image(&self, aov: Aov) -> Option<ImageBuffer<Rgb<u8>, Vec<u8>>> {
+        let pass = self.aov(aov)?;
+        let largest = pass
+            .pixels
+            .iter()
+            .map(|c| c.r)
+            .filter(|d| d.is_finite())
+            .fold(0.0, f64::max);
+        Some(ImageBuffer::from_fn(self.width, self.height, |x, y| {
+            aov.display_colour(pass.pixel(x, y), largest).into()
+        }))
+    }
+
+    /// Write the image as a single-part OpenEXR file with 32-bit float RGB
+    /// channels. AOVs are written to the same file, with their channels named
+    /// after them, like `depth.Z` or `normal.X`.
+    pub fn write_exr<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let channel = |name: &str, pixels: &[Colour], i: usize| {
+            let samples = pixels.iter().map(|c| [c.r, c.g, c.b][i] as f32).collect();
+            AnyChannel::new(name, FlatSamples::F32(samples))
+        };
+
+        let mut channels: Vec<_> = ["R", "G", "B"]
+            .iter()
+            .enumerate()
+            .map(|(i, name)| channel(name, &self.pixels, i))
+            .collect();
+        for (aov, pixels) in &self.aovs {
+            for (i, name) in aov.channels().iter().enumerate() {
+                channels.push(channel(&format!("{}.{}", aov, name), pixels, i));
+            }
+        }
+
+        let layer = Layer::new(
+            (self.width as usize, self.height as usize),
+            LayerAttributes::default(),
+            Encoding::FAST_LOSSLESS,
+            AnyChannels::sort(SmallVec::from_vec(channels)),
+        );
+        // Compressing in parallel can write the blocks in a different order
+        // each time, and renders should come out exactly the same.
+        Image::from_layer(layer)
+            .write()
+            .non_parallel()
+            .to_file(path)
+            .map_err(|e| io::Error::other(e.to_string()))
+    }
+
+    /// Write the image as a Radiance `.hdr` file. The format can't store
+    /// negative or infinite values, so they're written as 0.
+    pub fn write_hdr<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let f = |x: f64| {
+            if x.is_finite() {
+                x.max(0.0) as f32
+            } else {
+                0.0
+            }
+        };
+        let data: Vec<Rgb<f32>> = self
+            .pixels
+            .iter()
+            .map(|c| Rgb {
+                data: [f(c.r), f(c.g), f(c.b)],
+            })
+            .collect();
+        let file = BufWriter::new(File::create(path)?);
+        image::hdr::HDREncoder::new(file).encode(&data, self.width as usize, self.height as usize)
+    }
+
+    /// Write the image as a colour Portable Float Map.
+    pub fn write_pfm<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        // A negative scale means the samples are little-endian.
+        write!(file, "PF\n{} {}\n-1.0\n", self.width, self.height)?;
+        // PFM rows go from the bottom up.
+        for row in self.pixels.chunks(self.width as usize).rev() {
+            for c in row {
+                for channel in &[c.r, c.g, c.b] {
+                    file.write_all(&(*channel as f32).to_le_bytes())?;
+                }
+            }
+        }
+        file.flush()
+    }
+}
@@ -1,20 +1,32 @@
+use crate::aabb::Aabb;
 use crate::colour::Colour;
 use crate::material::{Material, Scatter};
 use crate::point::Point;
 use crate::ray::Ray;
+use crate::sampler::SampleStream;
 use crate::v3::V3;
 
 pub trait Hitable {
     fn is_hit_by(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit>;
+
+    /// A box containing everything the ray could hit.
+    fn bounds(&self) -> Aabb;
 }
 
 #[derive(Clone, Copy, Debug)]
 pub struct Hit {
     pub intersection: Point,
     pub normal: V3,
+    /// The normal of the surface itself, which smooth shading can bend
+    /// `normal` away from.
+    pub geometric_normal: V3,
     pub material: Material,
     // TODO: Can we maybe have a more descriptive name? (2019-02-03)
     pub t: f64,
+    /// Texture coordinates, for shapes that have them.
+    pub uv: (f64, f64),
+    /// The index of the object in the scene that was hit.
+    pub object: usize,
 }
 
 impl Hit {
@@ -22,12 +34,49 @@ impl Hit {
         Hit {
             intersection,
             normal,
+            geometric_normal: normal,
             material,
             t,
+            uv: (0.0, 0.0),
+            object: 0,
+        }
+    }
+
+    pub fn with_uv(mut self, u: f64, v: f64) -> Self {
+        self.uv = (u, v);
+        self
+    }
+
+    pub fn with_geometric_normal(mut self, normal: V3) -> Self {
+        self.geometric_normal = normal;
+        self
+    }
+
+    pub fn with_object(mut self, object: usize) -> Self {
+        self.object = object;
+        self
+    }
+
+    /// The normal flipped, if needed, to be on the same side of the surface
+    /// as the ray came from. Triangles can be hit from either side, unlike
+    /// spheres which (from the outside) are always hit from the front.
+    pub fn normal_against(&self, ray: &Ray) -> V3 {
+        if ray.direction().dot(self.normal) > 0.0 {
+            -self.normal
+        } else {
+            self.normal
         }
     }
 
-    pub fn scatter(&self, ray: &Ray) -> Option<(Colour, Ray)> {
-        self.material.scatter(ray, self)
+    pub fn scatter(&self, ray: &Ray, samples: &mut SampleStream) -> Option<(Colour, Ray)> {
+        self.material.scatter(ray, self, samples)
+    }
+
+    pub fn evaluate(&self, ray: &Ray, direction: V3) -> Option<(Colour, f64)> {
+        self.material.evaluate(ray, self, direction)
+    }
+
+    pub fn emitted(&self, ray: &Ray) -> Colour {
+        self.material.emitted(ray, self)
     }
 }
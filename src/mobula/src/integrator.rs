@@ -0,0 +1,288 @@
+//! The algorithms which work out how much light arrives along a camera ray.
+//!
+//! Path tracing is the one that gets the lighting right. The others leave
+//! parts of it out, which makes them quicker and useful for checking a
+//! scene's geometry and lights.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use std::f64::consts::PI;
+use std::str::FromStr;
+
+use crate::colour::Colour;
+use crate::hit::Hit;
+use crate::named::{self, Named};
+use crate::ray::Ray;
+use crate::sampler::SampleStream;
+use crate::scene::{power_heuristic, Scene};
+use crate::v3::V3;
+
+/// How many bounces a path gets before it can be ended by Russian roulette.
+/// The first few carry most of the light, so they're always taken.
+const ROULETTE_DEPTH: u32 = 3;
+
+// This would be nicer as a trait, but like `Shape` it needs to be read from
+// scene files. Those can give just the name of one without any settings.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(remote = "Self", tag = "type")]
+#[serde(rename_all = "snake_case")]
+pub enum Integrator {
+    /// Follow each path as it bounces around the scene, picking up all the
+    /// light it finds.
+    #[default]
+    Path,
+    /// Only the light reaching the first diffuse surface straight from the
+    /// lights and background, after any mirror or glass bounces.
+    Direct,
+    /// Mirrors and glass are followed, and diffuse surfaces are only lit by
+    /// shadow rays to the lights. Light which can't be aimed at, like a plain
+    /// background's, doesn't reach them.
+    Whitted,
+    /// How much of the sky each surface can see, in grey. With a `distance`,
+    /// only things nearer than that count as blocking it.
+    AmbientOcclusion {
+        #[serde(default)]
+        distance: Option<f64>,
+    },
+}
+
+impl Integrator {
+    pub const NAMES: [&'static str; 4] = ["path", "direct", "whitted", "ambient_occlusion"];
+
+    /// The light arriving along a ray.
+    pub(crate) fn radiance(self, scene: &Scene, ray: Ray, samples: &mut SampleStream) -> Colour {
+        match self {
+            Integrator::Path => path(scene, ray, None, samples),
+            Integrator::Direct => path(scene, ray, Some(1), samples),
+            Integrator::Whitted => whitted(scene, ray, samples),
+            Integrator::AmbientOcclusion { distance } => {
+                ambient_occlusion(scene, ray, distance.unwrap_or(f64::MAX), samples)
+            }
+        }
+    }
+}
+
+impl FromStr for Integrator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "path" => Ok(Integrator::Path),
+            "direct" => Ok(Integrator::Direct),
+            "whitted" => Ok(Integrator::Whitted),
+            "ambient_occlusion" => Ok(Integrator::AmbientOcclusion { distance: None }),
+            _ => Err(format!("unknown integrator '{}'", s)),
+        }
+    }
+}
+
+impl Named for Integrator {
+    const WHAT: &'static str = "integrator";
+    const NAMES: &'static [&'static str] = &Integrator::NAMES;
+
+    fn read_tagged<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Integrator::deserialize(deserializer)
+    }
+}
+
+impl Serialize for Integrator {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Integrator::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Integrator {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        named::read(deserializer)
+    }
+}
+
+/// Path tracing, stopping after `diffuse_bounces` diffuse bounces if that's
+/// set.
+///
+/// The path is followed one bounce at a time, keeping track of how much of
+/// the light found further along it makes it back to the camera. After
+/// `ROULETTE_DEPTH` bounces, paths which carry little light are ended at
+/// random, and the ones that carry on are brightened to make up for them.
+/// `config.depth` is only a hard limit on how long a path can get.
+fn path(
+    scene: &Scene,
+    mut ray: Ray,
+    diffuse_bounces: Option<u32>,
+    samples: &mut SampleStream,
+) -> Colour {
+    let config = &scene.config;
+    let mut light = Colour::black();
+    let mut throughput = Colour::white();
+    // When the last bounce was diffuse, and so also sampled the background
+    // and lights directly, its density is kept so their light isn't counted
+    // twice.
+    let mut scatter_pdf = None;
+    let mut diffuse = 0;
+
+    for depth in 0..=config.depth {
+        let hit = match scene.nearest_hit(&ray, 0.001, f64::MAX) {
+            Some(hit) => hit,
+            None => return light + throughput * background(scene, &ray, scatter_pdf),
+        };
+
+        let emitted = match scatter_pdf {
+            Some(pdf) if config.light_sampling => {
+                hit.emitted(&ray) * power_heuristic(pdf, scene.emitters().pdf(&ray, &hit))
+            }
+            _ => hit.emitted(&ray),
+        };
+        light = light + throughput * emitted;
+        if depth == config.depth || Some(diffuse) == diffuse_bounces {
+            break;
+        }
+
+        let (attenuation, scattered) = match hit.scatter(&ray, samples) {
+            Some(scatter) => scatter,
+            None => break,
+        };
+
+        scatter_pdf = match hit.evaluate(&ray, scattered.direction()) {
+            Some((_, pdf)) => {
+                let direct = scene.direct_light(&ray, &hit, true, samples);
+                light = light + throughput * direct;
+                diffuse += 1;
+                Some(pdf)
+            }
+            None => None,
+        };
+
+        throughput = throughput * attenuation;
+        if depth + 1 >= ROULETTE_DEPTH {
+            let survival = throughput.r.max(throughput.g).max(throughput.b).min(1.0);
+            if survival <= 0.0 || samples.next_1d() >= survival {
+                break;
+            }
+            throughput = throughput * (1.0 / survival);
+        }
+        ray = scattered;
+    }
+
+    light
+}
+
+/// Whitted-style ray tracing: mirror and glass bounces are followed, and the
+/// first diffuse surface is lit only by shadow rays.
+fn whitted(scene: &Scene, mut ray: Ray, samples: &mut SampleStream) -> Colour {
+    let mut light = Colour::black();
+    let mut throughput = Colour::white();
+
+    for depth in 0..=scene.config.depth {
+        let hit = match scene.nearest_hit(&ray, 0.001, f64::MAX) {
+            Some(hit) => hit,
+            None => return light + throughput * background(scene, &ray, None),
+        };
+
+        light = light + throughput * hit.emitted(&ray);
+        if depth == scene.config.depth {
+            break;
+        }
+
+        let (attenuation, scattered) = match hit.scatter(&ray, samples) {
+            Some(scatter) => scatter,
+            None => break,
+        };
+        if hit.evaluate(&ray, scattered.direction()).is_some() {
+            let direct = scene.direct_light(&ray, &hit, false, samples);
+            light = light + throughput * direct;
+            break;
+        }
+
+        throughput = throughput * attenuation;
+        ray = scattered;
+    }
+
+    light
+}
+
+/// The fraction of rays from the first surface hit, spread out like the
+/// light a diffuse surface reflects, which don't hit anything within
+/// `distance`. Rays which don't hit anything at all see white.
+fn ambient_occlusion(scene: &Scene, ray: Ray, distance: f64, samples: &mut SampleStream) -> Colour {
+    let hit = match scene.nearest_hit(&ray, 0.001, f64::MAX) {
+        Some(hit) => hit,
+        None => return Colour::white(),
+    };
+
+    let direction = cosine_direction(&hit, &ray, samples.next_2d());
+    let occlusion = Ray::new(hit.intersection, direction);
+    if scene.nearest_hit(&occlusion, 0.001, distance).is_some() {
+        Colour::black()
+    } else {
+        Colour::white()
+    }
+}
+
+/// The light from the background seen by a ray which missed everything. If
+/// the ray was scattered from a diffuse surface with density `scatter_pdf`,
+/// that surface has already sampled the background and the sun.
+fn background(scene: &Scene, ray: &Ray, scatter_pdf: Option<f64>) -> Colour {
+    let background = scene.background.colour(ray);
+    match scatter_pdf {
+        Some(pdf) => {
+            let light_pdf = scene.background.pdf(ray.direction());
+            background * power_heuristic(pdf, light_pdf)
+        }
+        // Only rays that weren't scattered diffusely can see the sun, as the
+        // others have already sampled it.
+        None => scene
+            .lights()
+            .fold(background, |sum, light| sum + light.seen(ray.direction())),
+    }
+}
+
+/// A direction on the side of the surface the ray came from, picked with a
+/// density in proportion to its cosine with the normal.
+fn cosine_direction(hit: &Hit, ray: &Ray, (u, v): (f64, f64)) -> V3 {
+    let normal = hit.normal_against(ray);
+    let (s, t) = normal.orthonormal_basis();
+    let r = u.sqrt();
+    let phi = 2.0 * PI * v;
+    s * (r * phi.cos()) + t * (r * phi.sin()) + normal * (1.0 - u).max(0.0).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(json: &str) -> Result<Integrator, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    #[test]
+    fn reads_names_and_objects() {
+        assert_eq!(read(r#""path""#).unwrap(), Integrator::Path);
+        assert_eq!(read(r#""whitted""#).unwrap(), Integrator::Whitted);
+        assert_eq!(
+            read(r#""ambient_occlusion""#).unwrap(),
+            Integrator::AmbientOcclusion { distance: None }
+        );
+        assert_eq!(read(r#"{"type": "direct"}"#).unwrap(), Integrator::Direct);
+        assert_eq!(
+            read(r#"{"type": "ambient_occlusion", "distance": 2.5}"#).unwrap(),
+            Integrator::AmbientOcclusion {
+                distance: Some(2.5)
+            }
+        );
+        assert!(read(r#""pth""#).is_err());
+        assert!(read(r#"{"type": "pth"}"#).is_err());
+    }
+
+    #[test]
+    fn round_trip() {
+        for integrator in [
+            Integrator::Path,
+            Integrator::AmbientOcclusion {
+                distance: Some(0.5),
+            },
+        ] {
+            let json = serde_json::to_string(&integrator).unwrap();
+            assert_eq!(read(&json).unwrap(), integrator);
+        }
+    }
+}
@@ -3,14 +3,35 @@
 // TODO: Document why? (2019-02-01)
 #![allow(clippy::cast_lossless)]
 
+pub mod adaptive;
+pub mod aov;
+pub mod background;
 pub mod camera;
+pub mod cancel;
+pub mod checkpoint;
 pub mod colour;
 pub mod config;
+pub mod environment;
+pub mod filter;
+pub mod framebuffer;
+pub mod integrator;
+pub mod light;
 pub mod material;
 pub mod point;
+pub mod progress;
+pub mod random;
 pub mod ray;
+pub mod sampler;
 pub mod scene;
 pub mod shape;
+pub mod sky;
+pub mod tile;
+pub mod tone_map;
 
+mod aabb;
+mod bvh;
+mod distribution;
+mod emitter;
 mod hit;
+mod named;
 mod v3;
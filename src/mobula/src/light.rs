@@ -0,0 +1,206 @@
+//! Lights which aren't objects in the scene: points, spotlights and the sun.
+//!
+//! They can't be seen or hit by rays, so the only way their light gets into
+//! the image is by tracing a shadow ray towards each of them from every
+//! diffuse hit.
+
+use serde::{Deserialize, Serialize};
+
+use std::f64::consts::PI;
+
+use crate::colour::Colour;
+use crate::point::Point;
+use crate::sampler::SampleStream;
+use crate::v3::V3;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+#[serde(rename_all = "snake_case")]
+pub enum Light {
+    /// Light shining equally in every direction from a point, falling off
+    /// with the square of the distance.
+    Point {
+        position: Point,
+        #[serde(default = "Light::default_colour")]
+        colour: Colour,
+        #[serde(default = "Light::default_intensity")]
+        intensity: f64,
+    },
+    /// A point light shining in a cone around `direction`. It's at full
+    /// strength within `inner_angle` of the direction, and fades smoothly
+    /// to nothing at `outer_angle`, both in degrees.
+    Spot {
+        position: Point,
+        direction: V3,
+        #[serde(default = "Light::default_colour")]
+        colour: Colour,
+        #[serde(default = "Light::default_intensity")]
+        intensity: f64,
+        #[serde(default = "Light::default_inner_angle")]
+        inner_angle: f64,
+        #[serde(default = "Light::default_outer_angle")]
+        outer_angle: f64,
+    },
+    /// Light from very far away, like the sun, all travelling in
+    /// `direction`. With an angular radius in degrees it comes from a disc
+    /// that size in the sky instead, which softens the shadows.
+    Directional {
+        direction: V3,
+        #[serde(default = "Light::default_colour")]
+        colour: Colour,
+        #[serde(default = "Light::default_intensity")]
+        intensity: f64,
+        #[serde(default)]
+        angular_radius: f64,
+    },
+}
+
+impl Light {
+    pub fn point(position: Point, colour: Colour, intensity: f64) -> Self {
+        Light::Point {
+            position,
+            colour,
+            intensity,
+        }
+    }
+
+    pub fn spot(position: Point, direction: V3, colour: Colour, intensity: f64) -> Self {
+        Light::Spot {
+            position,
+            direction,
+            colour,
+            intensity,
+            inner_angle: Light::default_inner_angle(),
+            outer_angle: Light::default_outer_angle(),
+        }
+    }
+
+    pub fn directional(direction: V3, colour: Colour, intensity: f64) -> Self {
+        Light::Directional {
+            direction,
+            colour,
+            intensity,
+            angular_radius: 0.0,
+        }
+    }
+
+    /// The direction from `from` to the light, as a unit vector, how far
+    /// away the light is, and the light arriving from it, unless none does.
+    /// Only suns with an angular radius use up any of `samples`.
+    pub(crate) fn illuminate(
+        &self,
+        from: Point,
+        samples: &mut SampleStream,
+    ) -> Option<(V3, f64, Colour)> {
+        let (direction, distance, light) = match *self {
+            Light::Point {
+                position,
+                colour,
+                intensity,
+            } => {
+                let (direction, distance) = towards(from, position);
+                let light = colour * (intensity / (distance * distance));
+                (direction, distance, light)
+            }
+            Light::Spot {
+                position,
+                direction: axis,
+                colour,
+                intensity,
+                inner_angle,
+                outer_angle,
+            } => {
+                let (direction, distance) = towards(from, position);
+                let cosine = (-direction).dot(axis.normalize());
+                let cos_outer = outer_angle.to_radians().cos();
+                let cos_inner = inner_angle.to_radians().cos().max(cos_outer);
+                let falloff = smoothstep(cos_outer, cos_inner, cosine);
+                let light = colour * (intensity * falloff / (distance * distance));
+                (direction, distance, light)
+            }
+            Light::Directional {
+                direction,
+                colour,
+                intensity,
+                angular_radius,
+            } => {
+                let axis = -direction.normalize();
+                let direction = if angular_radius > 0.0 {
+                    // Pick a direction evenly from the disc of the sun. The
+                    // light from each is the light of the whole sun divided
+                    // by its solid angle, which the density cancels out.
+                    let (u, v) = samples.next_2d();
+                    let cos_max = angular_radius.min(90.0).to_radians().cos();
+                    let cos_theta = 1.0 - u + u * cos_max;
+                    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+                    let phi = 2.0 * PI * v;
+                    let (s, t) = axis.orthonormal_basis();
+                    s * (sin_theta * phi.cos()) + t * (sin_theta * phi.sin()) + axis * cos_theta
+                } else {
+                    axis
+                };
+                (direction, f64::MAX, colour * intensity)
+            }
+        };
+
+        if light.luminance() > 0.0 && distance > 0.0 {
+            Some((direction, distance, light))
+        } else {
+            None
+        }
+    }
+
+    /// The light seen looking along `direction` straight at the light. Only
+    /// suns with an angular radius can be seen, as a disc in the sky.
+    pub(crate) fn seen(&self, direction: V3) -> Colour {
+        match *self {
+            Light::Directional {
+                direction: travelling,
+                colour,
+                intensity,
+                angular_radius,
+            } if angular_radius > 0.0 => {
+                let cos_max = angular_radius.min(90.0).to_radians().cos();
+                if direction.normalize().dot(-travelling.normalize()) >= cos_max {
+                    // The light of the whole sun, spread over its disc.
+                    colour * (intensity / (2.0 * PI * (1.0 - cos_max)))
+                } else {
+                    Colour::black()
+                }
+            }
+            _ => Colour::black(),
+        }
+    }
+
+    fn default_colour() -> Colour {
+        Colour::white()
+    }
+
+    fn default_intensity() -> f64 {
+        1.0
+    }
+
+    fn default_inner_angle() -> f64 {
+        20.0
+    }
+
+    fn default_outer_angle() -> f64 {
+        30.0
+    }
+}
+
+/// The unit vector from `from` to `to`, and how far apart they are.
+fn towards(from: Point, to: Point) -> (V3, f64) {
+    let offset = to - from;
+    let distance = offset.magnitude();
+    (offset * (1.0 / distance), distance)
+}
+
+/// 0 below `low`, 1 above `high`, and an S-shaped curve between.
+fn smoothstep(low: f64, high: f64, x: f64) -> f64 {
+    if high <= low {
+        return if x >= high { 1.0 } else { 0.0 };
+    }
+    let t = ((x - low) / (high - low)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
@@ -4,9 +4,10 @@ use crate::colour::Colour;
 use crate::hit::Hit;
 use crate::material::Scatter;
 use crate::ray::Ray;
+use crate::sampler::SampleStream;
 use crate::v3::V3;
 
-#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Dialectric {
     refractive_index: f64,
 }
@@ -24,7 +25,7 @@ impl Dialectric {
 }
 
 impl Scatter for Dialectric {
-    fn scatter(self, ray: &Ray, hit: &Hit) -> Option<(Colour, Ray)> {
+    fn scatter(self, ray: &Ray, hit: &Hit, samples: &mut SampleStream) -> Option<(Colour, Ray)> {
         let reflected = ray.direction().reflect(hit.normal);
 
         let colour = Colour::white();
@@ -54,7 +55,7 @@ impl Scatter for Dialectric {
             }
         };
 
-        let rand: f64 = rand::random();
+        let rand = samples.next_1d();
 
         let scattered = Ray::new(
             hit.intersection,
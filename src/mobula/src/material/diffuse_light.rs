@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+
+use crate::colour::Colour;
+use crate::hit::Hit;
+use crate::material::Scatter;
+use crate::ray::Ray;
+use crate::sampler::SampleStream;
+
+/// A material which gives off light, and doesn't reflect any.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DiffuseLight {
+    colour: Colour,
+    #[serde(default = "DiffuseLight::default_strength")]
+    strength: f64,
+}
+
+impl DiffuseLight {
+    pub fn new(colour: Colour, strength: f64) -> DiffuseLight {
+        DiffuseLight { colour, strength }
+    }
+
+    fn default_strength() -> f64 {
+        1.0
+    }
+}
+
+impl Scatter for DiffuseLight {
+    fn scatter(self, _ray: &Ray, _hit: &Hit, _samples: &mut SampleStream) -> Option<(Colour, Ray)> {
+        None
+    }
+
+    fn emitted(self, _ray: &Ray, _hit: &Hit) -> Colour {
+        self.colour * self.strength
+    }
+
+    fn albedo(self) -> Colour {
+        self.colour.clamp()
+    }
+}
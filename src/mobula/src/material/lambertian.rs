@@ -4,8 +4,12 @@ use crate::colour::Colour;
 use crate::hit::Hit;
 use crate::material::Scatter;
 use crate::ray::Ray;
+use crate::sampler::SampleStream;
+use crate::v3::V3;
 
-#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+use std::f64::consts::PI;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Lambertian {
     albedo: Colour,
 }
@@ -17,12 +21,25 @@ impl Lambertian {
 }
 
 impl Scatter for Lambertian {
-    fn scatter(self, _ray: &Ray, hit: &Hit) -> Option<(Colour, Ray)> {
-        let target = hit.intersection + hit.normal + <dyn Scatter>::random_in_unit_sphere();
+    fn scatter(self, ray: &Ray, hit: &Hit, samples: &mut SampleStream) -> Option<(Colour, Ray)> {
+        let target = hit.intersection
+            + hit.normal_against(ray)
+            + <dyn Scatter>::on_unit_sphere(samples.next_2d());
 
         Some((
             self.albedo,
             Ray::new(hit.intersection, target - hit.intersection.into()),
         ))
     }
+
+    fn evaluate(self, ray: &Ray, hit: &Hit, direction: V3) -> Option<(Colour, f64)> {
+        // `scatter` picks directions with a cosine distribution, which is
+        // exactly in proportion to the light it reflects.
+        let cosine = hit.normal_against(ray).dot(direction.normalize()).max(0.0);
+        Some((self.albedo * (cosine / PI), cosine / PI))
+    }
+
+    fn albedo(self) -> Colour {
+        self.albedo
+    }
 }
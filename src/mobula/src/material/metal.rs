@@ -4,8 +4,9 @@ use crate::colour::Colour;
 use crate::hit::Hit;
 use crate::material::Scatter;
 use crate::ray::Ray;
+use crate::sampler::SampleStream;
 
-#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Metal {
     albedo: Colour,
     fuzz: f64,
@@ -18,16 +19,21 @@ impl Metal {
 }
 
 impl Scatter for Metal {
-    fn scatter(self, ray: &Ray, hit: &Hit) -> Option<(Colour, Ray)> {
-        let reflected = ray.direction().normalize().reflect(hit.normal);
+    fn scatter(self, ray: &Ray, hit: &Hit, samples: &mut SampleStream) -> Option<(Colour, Ray)> {
+        let normal = hit.normal_against(ray);
+        let reflected = ray.direction().normalize().reflect(normal);
         let scattered = Ray::new(
             hit.intersection,
-            reflected + (<dyn Scatter>::random_in_unit_sphere() * self.fuzz),
+            reflected + (<dyn Scatter>::on_unit_sphere(samples.next_2d()) * self.fuzz),
         );
-        if scattered.direction().dot(hit.normal) > 0.0 {
+        if scattered.direction().dot(normal) > 0.0 {
             Some((self.albedo, scattered))
         } else {
             None
         }
     }
+
+    fn albedo(self) -> Colour {
+        self.albedo
+    }
 }
@@ -1,41 +1,76 @@
-use rand;
 use serde::{Deserialize, Serialize};
 
 use crate::colour::Colour;
 use crate::hit::Hit;
 use crate::ray::Ray;
+use crate::sampler::SampleStream;
 use crate::v3::V3;
 
+use std::f64::consts::PI;
+
 mod dialectric;
+mod diffuse_light;
 mod lambertian;
 mod metal;
 
 use crate::material::dialectric::Dialectric;
+use crate::material::diffuse_light::DiffuseLight;
 use crate::material::lambertian::Lambertian;
 use crate::material::metal::Metal;
 
 pub trait Scatter {
-    fn scatter(self, ray: &Ray, hit: &Hit) -> Option<(Colour, Ray)>;
+    fn scatter(self, ray: &Ray, hit: &Hit, samples: &mut SampleStream) -> Option<(Colour, Ray)>;
+
+    /// For materials which scatter light in every direction, how much of the
+    /// light arriving from `direction` is scattered back along the ray
+    /// (including the cosine term), and the probability density of `scatter`
+    /// picking that direction. Materials which only scatter in particular
+    /// directions, like mirrors, return `None`.
+    fn evaluate(self, _ray: &Ray, _hit: &Hit, _direction: V3) -> Option<(Colour, f64)>
+    where
+        Self: Sized,
+    {
+        None
+    }
+
+    /// The light given off by the material where it's hit.
+    fn emitted(self, _ray: &Ray, _hit: &Hit) -> Colour
+    where
+        Self: Sized,
+    {
+        Colour::black()
+    }
+
+    /// The base colour of the surface, ignoring lighting, as used by
+    /// denoisers.
+    fn albedo(self) -> Colour
+    where
+        Self: Sized,
+    {
+        Colour::white()
+    }
 }
 
 impl dyn Scatter {
-    fn random_in_unit_sphere() -> V3 {
-        let mut p = V3::new(100.0, 0.0, 0.0);
-        while p.magnitude() >= 1.0 {
-            p = V3::new(rand::random(), rand::random(), rand::random()) * 2.0
-                - V3::new(1.0, 1.0, 1.0);
-        }
-        p.normalize()
+    /// Map a point in [0, 1)² to a point on the unit sphere, keeping the
+    /// points evenly spread out.
+    fn on_unit_sphere((u, v): (f64, f64)) -> V3 {
+        let z = 1.0 - 2.0 * u;
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * PI * v;
+        V3::new(r * phi.cos(), r * phi.sin(), z)
     }
 }
 
-#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type")]
 #[serde(rename_all = "snake_case")]
 pub enum Material {
     Lambertian(Lambertian),
     Metal(Metal),
     Dialectric(Dialectric),
+    #[serde(alias = "emissive")]
+    DiffuseLight(DiffuseLight),
 }
 
 impl Material {
@@ -49,14 +84,41 @@ impl Material {
     pub fn dialectric(refractive_index: f64) -> Self {
         Material::Dialectric(Dialectric::new(refractive_index))
     }
+    pub fn diffuse_light(r: f64, g: f64, b: f64, strength: f64) -> Self {
+        Material::DiffuseLight(DiffuseLight::new(Colour::new(r, g, b), strength))
+    }
 }
 
 impl Scatter for Material {
-    fn scatter(self, ray: &Ray, hit: &Hit) -> Option<(Colour, Ray)> {
+    fn scatter(self, ray: &Ray, hit: &Hit, samples: &mut SampleStream) -> Option<(Colour, Ray)> {
+        match self {
+            Material::Lambertian(m) => m.scatter(ray, hit, samples),
+            Material::Metal(m) => m.scatter(ray, hit, samples),
+            Material::Dialectric(m) => m.scatter(ray, hit, samples),
+            Material::DiffuseLight(m) => m.scatter(ray, hit, samples),
+        }
+    }
+
+    fn evaluate(self, ray: &Ray, hit: &Hit, direction: V3) -> Option<(Colour, f64)> {
+        match self {
+            Material::Lambertian(m) => m.evaluate(ray, hit, direction),
+            _ => None,
+        }
+    }
+
+    fn emitted(self, ray: &Ray, hit: &Hit) -> Colour {
+        match self {
+            Material::DiffuseLight(m) => m.emitted(ray, hit),
+            _ => Colour::black(),
+        }
+    }
+
+    fn albedo(self) -> Colour {
         match self {
-            Material::Lambertian(m) => m.scatter(ray, hit),
-            Material::Metal(m) => m.scatter(ray, hit),
-            Material::Dialectric(m) => m.scatter(ray, hit),
+            Material::Lambertian(m) => m.albedo(),
+            Material::Metal(m) => m.albedo(),
+            Material::Dialectric(m) => m.albedo(),
+            Material::DiffuseLight(m) => m.albedo(),
         }
     }
 }
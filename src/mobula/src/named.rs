@@ -0,0 +1,47 @@
+//! Reading settings which scene files can give either as just a name, like
+//! `"aces"`, or as an object with a `type` and its parameters, like
+//! `{"type": "extended_reinhard", "white": 8.0}`.
+//!
+//! The types derive their object form with `#[serde(remote = "Self")]`, which
+//! gives them their own `serialize` and `deserialize` functions rather than
+//! implementing the traits, so they can implement `Deserialize` with `read`
+//! instead. Names are read with `FromStr`, which gives the default parameters.
+
+use serde::de::{self, MapAccess, Visitor};
+use serde::Deserializer;
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+pub(crate) trait Named: FromStr + Sized {
+    /// What the type's called in error messages.
+    const WHAT: &'static str;
+    const NAMES: &'static [&'static str];
+
+    /// Read the object form.
+    fn read_tagged<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error>;
+}
+
+/// Read either a name or the object form.
+pub(crate) fn read<'de, D: Deserializer<'de>, T: Named>(deserializer: D) -> Result<T, D::Error> {
+    deserializer.deserialize_any(NamedVisitor(PhantomData))
+}
+
+struct NamedVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Named> Visitor<'de> for NamedVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the name of a {}, or an object with its type", T::WHAT)
+    }
+
+    fn visit_str<E: de::Error>(self, name: &str) -> Result<T, E> {
+        name.parse().map_err(|_| E::unknown_variant(name, T::NAMES))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<T, A::Error> {
+        T::read_tagged(de::value::MapAccessDeserializer::new(map))
+    }
+}
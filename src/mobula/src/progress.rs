@@ -0,0 +1,28 @@
+//! Telling whoever started a render how it's getting on.
+//!
+//! The library doesn't draw anything itself. Renders report to a `Progress`,
+//! which might draw a bar in a terminal, log lines for another program to
+//! read, or do nothing at all.
+
+/// Watches a render. Tiles are finished on many threads at once, so this
+/// has to be shareable between them. Everything does nothing by default.
+pub trait Progress: Sync {
+    /// The render is starting, and will finish `tiles` tiles over all of its
+    /// passes.
+    fn start(&self, _tiles: u64) {}
+
+    /// Pass `pass` of `passes` is starting, counting from 0.
+    fn pass(&self, _pass: u32, _passes: u32) {}
+
+    /// Another tile has been rendered.
+    fn tile(&self) {}
+
+    /// The render is over, either because it's done or it was cancelled.
+    fn finish(&self, _cancelled: bool) {}
+}
+
+/// Ignores everything.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Silent;
+
+impl Progress for Silent {}
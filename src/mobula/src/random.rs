@@ -0,0 +1,26 @@
+//! Seedable random numbers, so a scene renders exactly the same way every
+//! time no matter how the work is split between threads.
+//!
+//! Every sample of every pixel gets its own generator, picked by the scene's
+//! seed and the pixel and sample it's for, rather than sharing one.
+
+use rand::SeedableRng;
+use rand_pcg::Pcg32;
+
+/// The random number generator used for a sample.
+pub type Random = Pcg32;
+
+/// The generator for one sample of a pixel. Pixels are numbered in rows from
+/// the top left.
+pub fn for_sample(seed: u64, pixel: u64, sample: u64) -> Random {
+    Random::seed_from_u64(mix(mix(mix(seed) ^ pixel) ^ sample))
+}
+
+/// SplitMix64's finaliser, which spreads small differences in its input
+/// across all of its output.
+pub(crate) fn mix(x: u64) -> u64 {
+    let x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    let x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
@@ -0,0 +1,141 @@
+//! A tiling blue noise mask, made with Robert Ulichney's void-and-cluster
+//! method the first time it's needed.
+//!
+//! Neighbouring pixels of the mask have very different values, so shifting
+//! each pixel's samples by it spreads their error out as fine grain.
+
+use rand::Rng;
+use rand::SeedableRng;
+
+use std::sync::OnceLock;
+
+use crate::random::{self, Random};
+
+/// The width and height of the mask.
+const SIZE: usize = 64;
+
+/// How far, in pixels, points push each other away.
+const SIGMA: f64 = 1.9;
+
+/// The mask's value for a pixel, in [0, 1). Each dimension uses the mask
+/// shifted by a different amount, so they aren't correlated.
+pub fn value(x: u32, y: u32, dimension: u32) -> f64 {
+    static MASK: OnceLock<Vec<f64>> = OnceLock::new();
+    let mask = MASK.get_or_init(generate);
+
+    let shift = random::mix(u64::from(dimension));
+    let x = (x as usize + (shift as usize)) % SIZE;
+    let y = (y as usize + ((shift >> 32) as usize)) % SIZE;
+    mask[y * SIZE + x]
+}
+
+fn generate() -> Vec<f64> {
+    let n = SIZE * SIZE;
+    let mut field = Field::new();
+    let mut rng = Random::seed_from_u64(0);
+
+    // Start with a tenth of the pixels picked at random.
+    let initial = n / 10;
+    let mut placed = 0;
+    while placed < initial {
+        let p = rng.gen_range(0, n);
+        if !field.on[p] {
+            field.toggle(p);
+            placed += 1;
+        }
+    }
+
+    // Even them out by moving the most crowded point to the emptiest space,
+    // until that wouldn't move it.
+    for _ in 0..n {
+        let cluster = field.tightest_cluster();
+        field.toggle(cluster);
+        let void = field.largest_void();
+        field.toggle(void);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut rank = vec![0; n];
+
+    // The starting points are ranked by taking away the most crowded first.
+    let mut removing = field.clone();
+    for r in (0..initial).rev() {
+        let cluster = removing.tightest_cluster();
+        removing.toggle(cluster);
+        rank[cluster] = r;
+    }
+
+    // And the rest by filling in the emptiest spaces.
+    for r in initial..n {
+        let void = field.largest_void();
+        field.toggle(void);
+        rank[void] = r;
+    }
+
+    rank.into_iter()
+        .map(|r| (r as f64 + 0.5) / n as f64)
+        .collect()
+}
+
+/// Which pixels are on, and how crowded each pixel is by those that are.
+#[derive(Clone)]
+struct Field {
+    on: Vec<bool>,
+    energy: Vec<f64>,
+    // The energy a point adds to each pixel, by how far away it is.
+    kernel: Vec<f64>,
+}
+
+impl Field {
+    fn new() -> Self {
+        // Distances wrap around, so the mask tiles.
+        let wrap = |d: usize| d.min(SIZE - d) as f64;
+        let kernel = (0..SIZE * SIZE)
+            .map(|i| {
+                let (dx, dy) = (wrap(i % SIZE), wrap(i / SIZE));
+                (-(dx * dx + dy * dy) / (2.0 * SIGMA * SIGMA)).exp()
+            })
+            .collect();
+
+        Field {
+            on: vec![false; SIZE * SIZE],
+            energy: vec![0.0; SIZE * SIZE],
+            kernel,
+        }
+    }
+
+    fn toggle(&mut self, p: usize) {
+        self.on[p] = !self.on[p];
+        let sign = if self.on[p] { 1.0 } else { -1.0 };
+        let (px, py) = (p % SIZE, p / SIZE);
+        for q in 0..SIZE * SIZE {
+            let dx = (q % SIZE + SIZE - px) % SIZE;
+            let dy = (q / SIZE + SIZE - py) % SIZE;
+            self.energy[q] += sign * self.kernel[dy * SIZE + dx];
+        }
+    }
+
+    /// The point that's on with the most energy.
+    fn tightest_cluster(&self) -> usize {
+        (0..self.on.len())
+            .filter(|&p| self.on[p])
+            .fold(None, |best: Option<usize>, p| match best {
+                Some(b) if self.energy[b] >= self.energy[p] => Some(b),
+                _ => Some(p),
+            })
+            .unwrap_or(0)
+    }
+
+    /// The pixel that's off with the least energy.
+    fn largest_void(&self) -> usize {
+        (0..self.on.len())
+            .filter(|&p| !self.on[p])
+            .fold(None, |best: Option<usize>, p| match best {
+                Some(b) if self.energy[b] <= self.energy[p] => Some(b),
+                _ => Some(p),
+            })
+            .unwrap_or(0)
+    }
+}
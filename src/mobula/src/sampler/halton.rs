@@ -0,0 +1,43 @@
+//! The Halton sequence, which uses a different prime base for each dimension.
+//!
+//! The digits are shuffled differently for each pixel and dimension. Without
+//! that, the first few points in the bigger bases are all bunched up near 0.
+
+use super::{permute, ONE_MINUS_EPSILON};
+
+/// The bases of the dimensions we have. Dimensions past these use random
+/// numbers instead.
+const PRIMES: [u32; 64] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+    197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307,
+    311,
+];
+
+/// The point `index` of the sequence in one dimension, with its digits
+/// shuffled by `seed`, if there's a base for that dimension.
+pub fn sample(dimension: usize, index: u32, seed: u32) -> Option<f64> {
+    PRIMES
+        .get(dimension)
+        .map(|&base| scrambled_radical_inverse(base, index, seed))
+}
+
+/// Mirror the digits of `index`, written in `base`, about the radix point,
+/// shuffling each digit with a different permutation.
+fn scrambled_radical_inverse(base: u32, mut index: u32, seed: u32) -> f64 {
+    let inverse_base = 1.0 / f64::from(base);
+    let mut reversed = 0.0;
+    let mut scale = inverse_base;
+    let mut position: u32 = 0;
+    // A shuffled 0 usually isn't 0, so this keeps going after the index runs
+    // out until the digits are too small to matter.
+    while f64::from(base - 1) * scale >= f64::EPSILON {
+        let digit = index % base;
+        let shuffled = permute(digit, base, seed ^ position.wrapping_mul(0x9e37_79b9));
+        reversed += f64::from(shuffled) * scale;
+        scale *= inverse_base;
+        index /= base;
+        position += 1;
+    }
+    reversed.min(ONE_MINUS_EPSILON)
+}
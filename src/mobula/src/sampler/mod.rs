@@ -0,0 +1,233 @@
+//! Where in each pixel, on the lens, and in each bounce samples are taken.
+//!
+//! Every random choice a sample makes is a dimension, and a sampler picks the
+//! numbers for each dimension of every sample of a pixel. Picking them so
+//! they're spread out evenly, rather than purely at random, makes the image
+//! converge with fewer samples.
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use std::str::FromStr;
+
+use crate::random::{self, Random};
+
+mod blue_noise;
+mod halton;
+mod sobol;
+mod stratified;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sampler {
+    /// Independent random numbers for everything.
+    #[default]
+    Random,
+    /// Each dimension is split into a stratum per sample, and each sample is
+    /// jittered within its own stratum.
+    Stratified,
+    /// The Halton sequence, with its digits shuffled for each pixel.
+    Halton,
+    /// Pairs of dimensions from the Sobol sequence, Owen scrambled for each
+    /// pixel.
+    Sobol,
+    /// The Sobol sequence, shifted for each pixel by a blue noise mask so the
+    /// error that's left looks like fine, even grain instead of blotches.
+    BlueNoise,
+}
+
+impl Sampler {
+    pub const NAMES: [&'static str; 5] = ["random", "stratified", "halton", "sobol", "blue_noise"];
+}
+
+impl FromStr for Sampler {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(Sampler::Random),
+            "stratified" => Ok(Sampler::Stratified),
+            "halton" => Ok(Sampler::Halton),
+            "sobol" => Ok(Sampler::Sobol),
+            "blue_noise" => Ok(Sampler::BlueNoise),
+            _ => Err(format!("unknown sampler '{}'", s)),
+        }
+    }
+}
+
+/// The numbers for one sample of a pixel, handed out a dimension at a time.
+///
+/// Samples should ask for their numbers in the same order, so the same
+/// dimension is used for the same thing in each of them.
+pub struct SampleStream {
+    sampler: Sampler,
+    seed: u64,
+    x: u32,
+    y: u32,
+    pixel: u64,
+    index: u32,
+    count: u32,
+    dimension: u32,
+    // For the random parts of the other samplers, and dimensions they've run
+    // out of.
+    rng: Random,
+}
+
+impl SampleStream {
+    /// The stream for sample `index` of `count` at pixel (`x`, `y`) of an
+    /// image `width` pixels wide.
+    pub fn new(
+        sampler: Sampler,
+        seed: u64,
+        (x, y): (u32, u32),
+        width: u32,
+        index: u32,
+        count: u32,
+    ) -> Self {
+        let pixel = u64::from(y) * u64::from(width) + u64::from(x);
+        SampleStream {
+            sampler,
+            seed,
+            x,
+            y,
+            pixel,
+            index,
+            count: count.max(1),
+            dimension: 0,
+            rng: random::for_sample(seed, pixel, u64::from(index)),
+        }
+    }
+
+    /// A number in [0, 1).
+    pub fn next_1d(&mut self) -> f64 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+
+        match self.sampler {
+            Sampler::Random => self.rng.gen(),
+            Sampler::Stratified => {
+                let jitter = self.rng.gen();
+                stratified::sample_1d(self.index, self.count, self.hash(dimension), jitter)
+            }
+            Sampler::Halton => {
+                match halton::sample(dimension as usize, self.index, self.hash(dimension)) {
+                    Some(x) => x,
+                    None => self.rng.gen(),
+                }
+            }
+            Sampler::Sobol => sobol::sample_1d(self.index, self.hash(dimension)),
+            Sampler::BlueNoise => {
+                let x = sobol::sample_1d(self.index, self.global_hash(dimension));
+                shift(x, blue_noise::value(self.x, self.y, dimension))
+            }
+        }
+    }
+
+    /// A point in [0, 1)², which is better spread out than two calls to
+    /// `next_1d` for samplers that stratify in more than one dimension.
+    pub fn next_2d(&mut self) -> (f64, f64) {
+        let dimension = self.dimension;
+        self.dimension += 2;
+
+        match self.sampler {
+            Sampler::Random => (self.rng.gen(), self.rng.gen()),
+            Sampler::Stratified => {
+                let jitter = (self.rng.gen(), self.rng.gen());
+                stratified::sample_2d(self.index, self.count, self.hash(dimension), jitter)
+            }
+            Sampler::Halton => {
+                let d = dimension as usize;
+                match (
+                    halton::sample(d, self.index, self.hash(dimension)),
+                    halton::sample(d + 1, self.index, self.hash(dimension + 1)),
+                ) {
+                    (Some(x), Some(y)) => (x, y),
+                    _ => (self.rng.gen(), self.rng.gen()),
+                }
+            }
+            Sampler::Sobol => sobol::sample_2d(self.index, self.hash(dimension)),
+            Sampler::BlueNoise => {
+                let (x, y) = sobol::sample_2d(self.index, self.global_hash(dimension));
+                (
+                    shift(x, blue_noise::value(self.x, self.y, dimension)),
+                    shift(y, blue_noise::value(self.x, self.y, dimension + 1)),
+                )
+            }
+        }
+    }
+
+    /// A seed for scrambling a dimension of this pixel.
+    fn hash(&self, dimension: u32) -> u32 {
+        hash(&[self.seed, self.pixel, u64::from(dimension)])
+    }
+
+    /// A seed for scrambling a dimension of every pixel the same way.
+    fn global_hash(&self, dimension: u32) -> u32 {
+        hash(&[self.seed, u64::from(dimension)])
+    }
+}
+
+/// Cranley-Patterson rotation, which moves every point of a sequence over by
+/// the same amount, wrapping around at 1.
+fn shift(x: f64, offset: f64) -> f64 {
+    let shifted = x + offset;
+    let wrapped = if shifted >= 1.0 {
+        shifted - 1.0
+    } else {
+        shifted
+    };
+    wrapped.min(ONE_MINUS_EPSILON)
+}
+
+/// The largest `f64` less than 1.
+const ONE_MINUS_EPSILON: f64 = 1.0 - f64::EPSILON / 2.0;
+
+/// Hash some numbers together into a 32-bit seed.
+fn hash(values: &[u64]) -> u32 {
+    let h = values.iter().fold(0, |h, &v| random::mix(h ^ v));
+    (h >> 32) as u32
+}
+
+/// Where `i` goes in a shuffle of `0..length` picked by `seed`, without
+/// having to store the shuffle. This is from Andrew Kensler's "Correlated
+/// Multi-Jittered Sampling" (2013).
+fn permute(i: u32, length: u32, seed: u32) -> u32 {
+    if length <= 1 {
+        return 0;
+    }
+
+    let mut mask = length - 1;
+    mask |= mask >> 1;
+    mask |= mask >> 2;
+    mask |= mask >> 4;
+    mask |= mask >> 8;
+    mask |= mask >> 16;
+
+    // Hashing within the next power of two up and skipping anything too big
+    // keeps this a permutation.
+    let mut i = i;
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= seed >> 16;
+        i ^= (i & mask) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= seed >> 23;
+        i ^= (i & mask) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & mask) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & mask) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & mask) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= mask;
+        i ^= i >> 5;
+        if i < length {
+            break;
+        }
+    }
+    (i + seed % length) % length
+}
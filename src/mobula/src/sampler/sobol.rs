@@ -0,0 +1,74 @@
+//! The first two dimensions of the Sobol sequence, with hash-based Owen
+//! scrambling.
+//!
+//! Rather than using the higher dimensions of the sequence, which need big
+//! tables and aren't as well spread out, each pair of dimensions uses the
+//! first two with its own scrambling and its own shuffled order of points.
+//! This is from Brent Burley's "Practical Hash-based Owen Scrambling" (2020).
+
+/// A point in one dimension.
+pub fn sample_1d(index: u32, seed: u32) -> f64 {
+    let index = nested_uniform_scramble(index, seed);
+    let x = nested_uniform_scramble(sobol(index, 0), mix(seed, 1));
+    to_unit(x)
+}
+
+/// A point in two dimensions.
+pub fn sample_2d(index: u32, seed: u32) -> (f64, f64) {
+    let index = nested_uniform_scramble(index, seed);
+    let x = nested_uniform_scramble(sobol(index, 0), mix(seed, 1));
+    let y = nested_uniform_scramble(sobol(index, 1), mix(seed, 2));
+    (to_unit(x), to_unit(y))
+}
+
+/// A dimension of the unscrambled sequence, as a fixed point fraction.
+fn sobol(index: u32, dimension: usize) -> u32 {
+    match dimension {
+        // The first dimension is the van der Corput sequence.
+        0 => index.reverse_bits(),
+        // The second uses the primitive polynomial x + 1, whose direction
+        // numbers follow from each other.
+        _ => {
+            let mut result = 0;
+            let mut direction = 1 << 31;
+            let mut index = index;
+            while index != 0 {
+                if index & 1 != 0 {
+                    result ^= direction;
+                }
+                index >>= 1;
+                direction ^= direction >> 1;
+            }
+            result
+        }
+    }
+}
+
+/// Owen scrambling, which randomly swaps halves of every interval, all the
+/// way down, while keeping how well spread out the points are.
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+/// A hash where each bit depends only on the bits below it, so flipping it
+/// about reversed bits swaps intervals without mixing them.
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x ^= x.wrapping_mul(0x3d20_adea);
+    x = x.wrapping_add(seed);
+    x = x.wrapping_mul((seed >> 16) | 1);
+    x ^= x.wrapping_mul(0x0552_6c56);
+    x ^= x.wrapping_mul(0x53a2_2864);
+    x
+}
+
+/// A seed for another dimension, derived from the first.
+fn mix(seed: u32, n: u32) -> u32 {
+    let x = seed ^ n.wrapping_mul(0x9e37_79b9);
+    let x = (x ^ (x >> 16)).wrapping_mul(0x7feb_352d);
+    let x = (x ^ (x >> 15)).wrapping_mul(0x846c_a68b);
+    x ^ (x >> 16)
+}
+
+fn to_unit(x: u32) -> f64 {
+    f64::from(x) / 4_294_967_296.0
+}
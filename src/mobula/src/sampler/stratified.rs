@@ -0,0 +1,30 @@
+//! Stratified sampling, where each sample of a pixel gets its own stratum of
+//! every dimension and is jittered inside it.
+//!
+//! Which sample gets which stratum is shuffled differently for each dimension,
+//! so the dimensions aren't correlated with each other.
+
+use super::permute;
+
+/// A point in one dimension, for sample `index` of `count`.
+pub fn sample_1d(index: u32, count: u32, seed: u32, jitter: f64) -> f64 {
+    (f64::from(permute(index, count, seed)) + jitter) / f64::from(count)
+}
+
+/// A point in two dimensions, for sample `index` of `count`.
+///
+/// When `count` is square the strata are a grid of squares. Otherwise each
+/// dimension is stratified on its own, which is a Latin hypercube.
+pub fn sample_2d(index: u32, count: u32, seed: u32, jitter: (f64, f64)) -> (f64, f64) {
+    let side = (f64::from(count).sqrt() as u32).max(1);
+    if side * side == count {
+        let cell = permute(index, count, seed);
+        let x = (f64::from(cell % side) + jitter.0) / f64::from(side);
+        let y = (f64::from(cell / side) + jitter.1) / f64::from(side);
+        (x, y)
+    } else {
+        let x = sample_1d(index, count, seed, jitter.0);
+        let y = sample_1d(index, count, seed.rotate_left(16) ^ 0x5bd1_e995, jitter.1);
+        (x, y)
+    }
+}
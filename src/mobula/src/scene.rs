@@ -1,14 +1,33 @@
 use image::{ImageBuffer, Rgb};
-use indicatif;
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+
+use crate::aabb::Aabb;
+use crate::adaptive::Estimate;
+use crate::aov::Aov;
+use crate::background::Background;
+use crate::bvh::Bvh;
 use crate::camera::{Camera, CameraBuilder};
+use crate::cancel::CancelToken;
+use crate::checkpoint::Checkpoint;
 use crate::colour::Colour;
 use crate::config::Config;
+use crate::emitter::Emitters;
+use crate::filter::Filter;
+use crate::framebuffer::Framebuffer;
 use crate::hit::{Hit, Hitable};
+use crate::light::Light;
+use crate::material::{Material, Scatter};
+use crate::progress::{Progress, Silent};
 use crate::ray::Ray;
+use crate::sampler::SampleStream;
 use crate::shape::Shape;
+use crate::tile::{self, Tile};
+use crate::v3::V3;
 
 #[derive(Default, Serialize, Deserialize)]
 pub struct Scene {
@@ -17,7 +36,33 @@ pub struct Scene {
     #[serde(default)]
     pub camera: CameraBuilder,
     #[serde(default)]
-    pub objects: Vec<Shape>,
+    pub background: Background,
+    /// A BVH is built over the objects the first time it's needed, so they
+    /// can only be added to with `push`, which starts it again.
+    #[serde(default)]
+    objects: Vec<Shape>,
+    /// Lights which aren't objects, like points and the sun.
+    #[serde(default)]
+    pub lights: Vec<Light>,
+    #[serde(skip)]
+    world: OnceLock<World>,
+}
+
+/// What's worked out from the objects before they can be rendered.
+struct World {
+    // The index of each object in the scene's list.
+    bvh: Bvh<usize>,
+    emitters: Emitters,
+}
+
+impl World {
+    fn new(objects: &[Shape]) -> Self {
+        let bounds: Vec<Aabb> = objects.iter().map(Hitable::bounds).collect();
+        World {
+            bvh: Bvh::from_bounds((0..objects.len()).collect(), &bounds),
+            emitters: Emitters::new(objects),
+        }
+    }
 }
 
 impl Scene {
@@ -35,104 +80,721 @@ impl Scene {
         self
     }
 
+    pub fn background(mut self, background: Background) -> Self {
+        self.background = background;
+        self
+    }
+
     pub fn push(mut self, object: Shape) -> Self {
         self.objects.push(object);
+        self.world = OnceLock::new();
         self
     }
 
+    pub fn objects(&self) -> &[Shape] {
+        &self.objects
+    }
+
+    pub fn push_light(mut self, light: Light) -> Self {
+        self.lights.push(light);
+        self
+    }
+
+    /// The analytic lights, and the sun if the background has one.
+    pub(crate) fn lights(&self) -> impl Iterator<Item = Light> + '_ {
+        self.lights.iter().copied().chain(self.background.sun())
+    }
+
+    fn world(&self) -> &World {
+        self.world.get_or_init(|| World::new(&self.objects))
+    }
+
+    /// The objects which give off light.
+    pub(crate) fn emitters(&self) -> &Emitters {
+        &self.world().emitters
+    }
+
     pub fn nearest_hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
-        let mut hit = None;
-        let mut closest_so_far = t_max;
-        for obj in &self.objects {
-            match obj.is_hit_by(ray, t_min, closest_so_far) {
-                None => {}
-                Some(nearer_hit) => {
-                    closest_so_far = nearer_hit.t;
-                    hit = Some(nearer_hit);
-                }
-            }
-        }
-        hit
+        self.world()
+            .bvh
+            .traverse(ray, t_min, t_max, |_, &index, closest_so_far| {
+                self.objects[index]
+                    .is_hit_by(ray, t_min, closest_so_far)
+                    .map(|hit| hit.with_object(index))
+            })
+    }
+
+    pub fn render_par(&self) -> Framebuffer {
+        self.render_progressive(None, &CancelToken::new(), &Silent, |_| ())
     }
 
-    pub fn render_par(&self) -> Vec<Colour> {
+    /// Render the image in passes of `config.progressive` samples per pixel,
+    /// or all at once if that isn't set, calling `after_pass` with the render
+    /// so far after each pass. If there's a checkpoint, the render carries on
+    /// from it, and stops at its end if it has one.
+    ///
+    /// If `cancel` is cancelled the render stops, part way through a pass if
+    /// need be, and the image is made from the samples taken so far. Every
+    /// pixel still gets at least one sample, so there's always an image to
+    /// show. The framebuffer has how many samples each pixel got.
+    ///
+    /// A pass that's cut short isn't passed to `after_pass`, so checkpoints
+    /// only ever have whole passes. Instead, the last whole pass is passed
+    /// again as the last one, so it can be saved.
+    ///
+    /// How it's getting on is reported to `progress`.
+    pub fn render_progressive<F>(
+        &self,
+        checkpoint: Option<Checkpoint>,
+        cancel: &CancelToken,
+        progress: &dyn Progress,
+        mut after_pass: F,
+    ) -> Framebuffer
+    where
+        F: FnMut(&Snapshot),
+    {
         let width = self.config.width;
-        let height = self.config.height;
         let camera = self.camera.build(&self.config);
 
-        let progress_bar = indicatif::ProgressBar::new((width * height) as u64);
-        progress_bar.set_draw_delta(1000);
-        progress_bar.set_style(
-            indicatif::ProgressStyle::default_bar()
-                .template("{msg} {elapsed} [{wide_bar}] {eta_precise} ({pos:>7}/{len:7})")
-                .progress_chars("=> "),
+        let mut checkpoint = checkpoint.unwrap_or_else(|| Checkpoint::new(self));
+        let first_sample = checkpoint.samples();
+        let max_samples = match checkpoint.end() {
+            Some(end) => end.min(self.config.max_samples()),
+            None => self.config.max_samples(),
+        };
+        let pass_samples = self.config.progressive.unwrap_or(max_samples).max(1);
+        let passes = max_samples
+            .saturating_sub(first_sample)
+            .div_ceil(pass_samples);
+
+        let tiles = tile::tiles(
+            checkpoint.region(),
+            self.config.tile_size,
+            self.config.tile_order,
         );
-        progress_bar.set_message("rendering");
 
-        let bytes = (0..(self.config.width * self.config.height))
-            .into_par_iter()
-            .inspect(|_| progress_bar.inc(1))
-            .map(|i| {
-                let x = i % width;
-                let y = (i - x) / width;
-                self.render_pixel(x, y, &camera)
+        progress.start(tiles.len() as u64 * passes as u64);
+
+        // The AOVs don't get any better with more passes, so they're only
+        // rendered once.
+        let surfaces = self.render_surfaces(&camera, cancel);
+
+        let mut cancelled = false;
+        let mut finished_pass = false;
+        let mut cut_short = Vec::new();
+        for pass in 0..passes {
+            // After the first pass every pixel has a sample.
+            if pass > 0 && cancel.is_cancelled() {
+                cancelled = true;
+                break;
+            }
+            progress.pass(pass, passes);
+            let start = first_sample + pass * pass_samples;
+            let samples = start..(start + pass_samples).min(max_samples);
+
+            // Tiles are handed out in order to threads as they become free,
+            // and each is rendered into its own buffer. The buffers are added
+            // up in order afterwards so the result doesn't depend on which
+            // thread got which tile.
+            let next = AtomicUsize::new(0);
+            let mut buffers: Vec<TileBuffer> = (0..rayon::current_num_threads())
+                .into_par_iter()
+                .flat_map_iter(|_| {
+                    let mut rendered = Vec::new();
+                    while let Some(&tile) = tiles.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        if pass > 0 && cancel.is_cancelled() {
+                            break;
+                        }
+                        let buffer = self.render_tile(
+                            tile,
+                            samples.clone(),
+                            &checkpoint.estimates,
+                            &camera,
+                            cancel,
+                        );
+                        rendered.push(buffer);
+                        progress.tile();
+                    }
+                    rendered
+                })
+                .collect();
+            buffers.sort_by_key(|buffer| (buffer.tile.top, buffer.tile.left));
+
+            if cancel.is_cancelled() {
+                // Kept out of the checkpoint until the last whole pass has
+                // been passed on.
+                cancelled = true;
+                cut_short = buffers;
+                break;
+            }
+            for buffer in buffers {
+                buffer.add_to(&mut checkpoint, width);
+            }
+            checkpoint.set_samples(samples.end);
+
+            after_pass(&Snapshot {
+                scene: self,
+                checkpoint: &checkpoint,
+                surfaces: &surfaces,
+                last: pass + 1 == passes,
+            });
+            finished_pass = true;
+        }
+
+        if cancelled && finished_pass {
+            after_pass(&Snapshot {
+                scene: self,
+                checkpoint: &checkpoint,
+                surfaces: &surfaces,
+                last: true,
+            });
+        }
+        for buffer in cut_short {
+            buffer.add_to(&mut checkpoint, width);
+        }
+
+        progress.finish(cancelled);
+
+        self.framebuffer_with(&checkpoint, &surfaces)
+    }
+
+    /// The image from a render that's been saved, such as the partial renders
+    /// of an image merged together. Only the AOVs need to be rendered.
+    pub fn framebuffer(&self, checkpoint: &Checkpoint) -> Framebuffer {
+        let camera = self.camera.build(&self.config);
+        let surfaces = self.render_surfaces(&camera, &CancelToken::new());
+        self.framebuffer_with(checkpoint, &surfaces)
+    }
+
+    /// Render the samples in the range `samples` for each pixel of a tile,
+    /// carrying on from their `estimates`.
+    fn render_tile(
+        &self,
+        tile: Tile,
+        samples: Range<u32>,
+        estimates: &[Estimate],
+        camera: &Camera,
+        cancel: &CancelToken,
+    ) -> TileBuffer {
+        let filter = self.config.filter;
+        let mut buffer = TileBuffer::new(tile, &self.config);
+        for y in tile.top..tile.bottom {
+            for x in tile.left..tile.right {
+                let mut estimate = estimates[(y * self.config.width + x) as usize];
+                self.render_samples(
+                    x,
+                    y,
+                    samples.clone(),
+                    &mut estimate,
+                    camera,
+                    cancel,
+                    |position, colour| buffer.splat(filter, position, colour),
+                );
+                buffer.estimates.push(estimate);
+            }
+        }
+        buffer
+    }
+
+    /// The image made from the weighted sums of the samples splatted into
+    /// each pixel, with its AOVs.
+    fn framebuffer_with(&self, checkpoint: &Checkpoint, surfaces: &[Surface]) -> Framebuffer {
+        // Filters with negative lobes can leave pixels next to bright edges
+        // slightly negative, which isn't light that can be shown or stored.
+        let pixels = checkpoint
+            .sums
+            .iter()
+            .map(|&(colour, weight)| {
+                if weight > 0.0 {
+                    let c = colour * (1.0 / weight);
+                    Colour::new(c.r.max(0.0), c.g.max(0.0), c.b.max(0.0))
+                } else {
+                    Colour::black()
+                }
+            })
+            .collect();
+
+        let surfaces: Vec<Surface> = surfaces
+            .iter()
+            .zip(&checkpoint.estimates)
+            .map(|(&surface, estimate)| Surface {
+                samples: estimate.count(),
+                ..surface
             })
             .collect();
 
-        progress_bar.finish_with_message("complete in");
-        bytes
+        let samples = checkpoint.estimates.iter().map(Estimate::count).collect();
+        let mut framebuffer = Framebuffer::new(self.config.width, self.config.height, pixels)
+            .with_sample_counts(samples);
+        for &aov in &self.config.aovs {
+            framebuffer = framebuffer.with_aov(aov, aov_pass(aov, &surfaces));
+        }
+        framebuffer
     }
 
+    /// The average of a pixel's samples, ignoring the filter.
     pub fn render_pixel(&self, i: u32, j: u32, camera: &Camera) -> Colour {
+        let mut sum = Colour::black();
+        let mut estimate = Estimate::default();
+        let samples = 0..self.config.max_samples();
+        let cancel = CancelToken::new();
+        self.render_samples(
+            i,
+            j,
+            samples,
+            &mut estimate,
+            camera,
+            &cancel,
+            |_, colour| sum = sum + colour,
+        );
+        sum * (1.0 / estimate.count().max(1) as f64)
+    }
+
+    /// Render the samples of a pixel in the range `samples`, passing where on
+    /// the image each was taken (in pixels from the top left) and the light it
+    /// found to `splat`. With adaptive sampling this stops early once
+    /// `estimate`, which covers all the pixel's samples so far, is good enough,
+    /// and it always stops if `cancel` is cancelled, once the pixel has a
+    /// sample.
+    #[allow(clippy::too_many_arguments)]
+    fn render_samples<F>(
+        &self,
+        i: u32,
+        j: u32,
+        samples: Range<u32>,
+        estimate: &mut Estimate,
+        camera: &Camera,
+        cancel: &CancelToken,
+        mut splat: F,
+    ) where
+        F: FnMut((f64, f64), Colour),
+    {
+        for sample in samples {
+            if estimate.count() > 0 && cancel.is_cancelled() {
+                break;
+            }
+            if sample >= self.config.samples {
+                match self.config.adaptive {
+                    Some(adaptive) if estimate.relative_error() > adaptive.error => {}
+                    _ => break,
+                }
+            }
+            let (position, ray, mut samples) = self.camera_ray(i, j, sample, camera);
+            let colour = self.config.integrator.radiance(self, ray, &mut samples);
+            estimate.add(colour);
+            splat(position, colour);
+        }
+    }
+
+    /// The ray for one sample of a pixel, where on the image it's from, and
+    /// the stream of numbers to use for the rest of the sample.
+    fn camera_ray(
+        &self,
+        i: u32,
+        j: u32,
+        sample: u32,
+        camera: &Camera,
+    ) -> ((f64, f64), Ray, SampleStream) {
         let width = self.config.width;
         let height = self.config.height;
 
-        let u = (i as f64) / (width as f64);
-        let v = ((height - j) as f64) / (height as f64);
+        let mut samples = SampleStream::new(
+            self.config.sampler,
+            self.config.seed,
+            (i, j),
+            width,
+            sample,
+            self.config.max_samples(),
+        );
+
+        let (h_sample, v_sample) = samples.next_2d();
+        let (x, y) = (i as f64 + h_sample, j as f64 + v_sample);
+        let u = x / (width as f64);
+        let v = 1.0 - y / (height as f64);
+
+        ((x, y), camera.get_ray(u, v, &mut samples), samples)
+    }
 
-        let mut c = Colour::black();
-        for i in 0..self.config.samples {
-            let h_sample = rand::random::<f64>() / (width as f64);
-            let v_sample = rand::random::<f64>() / (height as f64);
+    /// What's first seen through each pixel, if there are any AOVs. When
+    /// `cancel` is cancelled, pixels stop at their first sample.
+    fn render_surfaces(&self, camera: &Camera, cancel: &CancelToken) -> Vec<Surface> {
+        let width = self.config.width;
+        if self.config.aovs.is_empty() {
+            return Vec::new();
+        }
+        (0..width * self.config.height)
+            .into_par_iter()
+            .map(|p| self.render_surface(p % width, p / width, camera, cancel))
+            .collect()
+    }
 
-            let ray = camera.get_ray(u + h_sample, v + v_sample);
+    /// What's first seen through a pixel, for the AOVs. This uses the same
+    /// camera rays as the image, so the two line up exactly.
+    fn render_surface(&self, i: u32, j: u32, camera: &Camera, cancel: &CancelToken) -> Surface {
+        let mut surface = Surface::default();
+        let mut normal = V3::zero();
+        let mut albedo = Colour::black();
+        let mut count = 0;
+        for sample in 0..self.config.samples {
+            if sample > 0 && cancel.is_cancelled() {
+                break;
+            }
+            count += 1;
+            let (_, ray, _) = self.camera_ray(i, j, sample, camera);
 
-            c = Colour::linear_interpolation(c, self.colour(ray, 0), 1.0 / ((i + 1) as f64));
+            match self.nearest_hit(&ray, 0.001, f64::MAX) {
+                Some(hit) => {
+                    let depth = camera.depth(&ray, hit.t);
+                    if depth < surface.depth {
+                        surface.depth = depth;
+                        surface.material = Some(hit.material);
+                        surface.object = Some(hit.object);
+                    }
+                    normal = normal + hit.normal;
+                    albedo = albedo + hit.material.albedo();
+                }
+                None => albedo = albedo + self.background.colour(&ray).clamp(),
+            }
         }
 
-        Colour::new(c.r.sqrt(), c.g.sqrt(), c.b.sqrt())
+        let samples = count.max(1) as f64;
+        surface.normal = normal * (1.0 / samples);
+        surface.albedo = albedo * (1.0 / samples);
+        surface
     }
 
     pub fn render(&self) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
-        let buf = self.render_par();
+        self.render_par().to_image(&self.config)
+    }
 
-        ImageBuffer::from_fn(self.config.width, self.config.height, |x, y| {
-            let pixel_index: usize = ((y * self.config.width) + x) as usize;
-            buf[pixel_index].into()
-        })
+    /// The light reaching a diffuse hit straight from the background and the
+    /// lights, found by tracing shadow rays to them. With `mis`, the light
+    /// from the background and emissive objects is weighted against the ray
+    /// scattered from the hit finding it too.
+    pub(crate) fn direct_light(
+        &self,
+        ray: &Ray,
+        hit: &Hit,
+        mis: bool,
+        samples: &mut SampleStream,
+    ) -> Colour {
+        let mut direct = Colour::black();
+        if self.background.can_be_sampled() {
+            direct = direct + self.sample_background(ray, hit, mis, samples);
+        }
+        if self.config.light_sampling && !self.emitters().is_empty() {
+            direct = direct + self.sample_emitter(ray, hit, mis, samples);
+        }
+        if self.lights().next().is_some() {
+            direct = direct + self.sample_lights(ray, hit, samples);
+        }
+        direct
     }
 
-    fn colour(&self, ray: Ray, depth: u32) -> Colour {
-        match self.nearest_hit(&ray, 0.001, std::f64::MAX) {
-            None => Scene::background(ray),
-            Some(hit) => {
-                if depth < self.config.depth {
-                    match hit.scatter(&ray) {
-                        Some((attenuation, scattered)) => {
-                            attenuation * self.colour(scattered, depth + 1)
-                        }
-                        None => Colour::black(),
-                    }
-                } else {
-                    Colour::black()
-                }
+    /// The light from the background reflected at a diffuse hit, found by
+    /// picking a direction towards the background rather than relying on a
+    /// scattered ray to find the bright parts of it.
+    fn sample_background(
+        &self,
+        ray: &Ray,
+        hit: &Hit,
+        mis: bool,
+        samples: &mut SampleStream,
+    ) -> Colour {
+        let u = samples.next_2d();
+        let (direction, radiance, light_pdf) = match self.background.sample(u) {
+            Some(sample) => sample,
+            None => return Colour::black(),
+        };
+        let (reflectance, scatter_pdf) = match hit.evaluate(ray, direction) {
+            Some(e) if e.1 > 0.0 => e,
+            _ => return Colour::black(),
+        };
+
+        let shadow = Ray::new(hit.intersection, direction);
+        if self.nearest_hit(&shadow, 0.001, f64::MAX).is_some() {
+            return Colour::black();
+        }
+
+        let weight = if mis {
+            power_heuristic(light_pdf, scatter_pdf)
+        } else {
+            1.0
+        };
+        reflectance * radiance * (weight / light_pdf)
+    }
+
+    /// The light from the scene's emissive objects reflected at a diffuse
+    /// hit, found by tracing a shadow ray to a point picked on one of them.
+    fn sample_emitter(
+        &self,
+        ray: &Ray,
+        hit: &Hit,
+        mis: bool,
+        samples: &mut SampleStream,
+    ) -> Colour {
+        let u = samples.next_1d();
+        let v = samples.next_2d();
+        let light = match self.emitters().sample(hit.intersection, u, v) {
+            Some(light) => light,
+            None => return Colour::black(),
+        };
+        let (reflectance, scatter_pdf) = match hit.evaluate(ray, light.direction) {
+            Some(e) if e.1 > 0.0 => e,
+            _ => return Colour::black(),
+        };
+
+        let shadow = Ray::new(hit.intersection, light.direction);
+        if self
+            .nearest_hit(&shadow, 0.001, light.distance - 0.001)
+            .is_some()
+        {
+            return Colour::black();
+        }
+
+        let radiance = light.hit.emitted(&shadow);
+        let weight = if mis {
+            power_heuristic(light.pdf, scatter_pdf)
+        } else {
+            1.0
+        };
+        reflectance * radiance * (weight / light.pdf)
+    }
+
+    /// The light from the scene's analytic lights, and the sky's sun,
+    /// reflected at a diffuse hit. Nothing else can find them, so every one
+    /// gets a shadow ray.
+    fn sample_lights(&self, ray: &Ray, hit: &Hit, samples: &mut SampleStream) -> Colour {
+        let mut total = Colour::black();
+        for light in self.lights() {
+            let (direction, distance, radiance) = match light.illuminate(hit.intersection, samples)
+            {
+                Some(light) => light,
+                None => continue,
+            };
+            let reflectance = match hit.evaluate(ray, direction) {
+                Some((reflectance, pdf)) if pdf > 0.0 => reflectance,
+                _ => continue,
+            };
+
+            let shadow = Ray::new(hit.intersection, direction);
+            if self.nearest_hit(&shadow, 0.001, distance - 0.001).is_none() {
+                total = total + reflectance * radiance;
+            }
+        }
+        total
+    }
+}
+
+/// The weight multiple importance sampling gives a sample picked by a
+/// strategy with density `a`, when another strategy could also have picked it
+/// with density `b`.
+pub(crate) fn power_heuristic(a: f64, b: f64) -> f64 {
+    let (a, b) = (a * a, b * b);
+    if a + b == 0.0 {
+        0.0
+    } else {
+        a / (a + b)
+    }
+}
+
+/// A render part way through, after one of its passes.
+pub struct Snapshot<'a> {
+    scene: &'a Scene,
+    checkpoint: &'a Checkpoint,
+    surfaces: &'a [Surface],
+    last: bool,
+}
+
+impl<'a> Snapshot<'a> {
+    /// The image so far.
+    pub fn framebuffer(&self) -> Framebuffer {
+        self.scene.framebuffer_with(self.checkpoint, self.surfaces)
+    }
+
+    /// Everything needed to carry on the render later.
+    pub fn checkpoint(&self) -> &Checkpoint {
+        self.checkpoint
+    }
+
+    /// Whether this is the last pass, after which the render is done or has
+    /// been cancelled.
+    pub fn is_last(&self) -> bool {
+        self.last
+    }
+}
+
+/// The samples splatted while rendering a tile, which can reach into the
+/// pixels around it as far as the filter's radius.
+struct TileBuffer {
+    tile: Tile,
+    // The pixels the samples can reach, clipped to the image.
+    area: Tile,
+    sums: Vec<(Colour, f64)>,
+    // The estimates of the tile's own pixels, row by row.
+    estimates: Vec<Estimate>,
+}
+
+impl TileBuffer {
+    fn new(tile: Tile, config: &Config) -> Self {
+        let reach = config.filter.radius().ceil() as u32;
+        let area = Tile {
+            left: tile.left.saturating_sub(reach),
+            top: tile.top.saturating_sub(reach),
+            right: (tile.right + reach).min(config.width),
+            bottom: (tile.bottom + reach).min(config.height),
+        };
+        let pixels = ((area.right - area.left) * (area.bottom - area.top)) as usize;
+        TileBuffer {
+            tile,
+            area,
+            sums: vec![(Colour::black(), 0.0); pixels],
+            estimates: Vec::with_capacity(
+                ((tile.right - tile.left) * (tile.bottom - tile.top)) as usize,
+            ),
+        }
+    }
+
+    /// Add a sample to every pixel whose centre is within the filter's radius.
+    fn splat(&mut self, filter: Filter, (x, y): (f64, f64), colour: Colour) {
+        let radius = filter.radius();
+        // The pixels whose centres are in [x - radius, x + radius), clipped
+        // to the area.
+        let range = |p: f64, low: u32, high: u32| {
+            let first = ((p - 0.5 - radius).floor() + 1.0).max(low as f64) as u32;
+            let last = ((p - 0.5 + radius).floor() + 1.0).min(high as f64).max(0.0) as u32;
+            first..last
+        };
+
+        let area = self.area;
+        let width = area.right - area.left;
+        for py in range(y, area.top, area.bottom) {
+            for px in range(x, area.left, area.right) {
+                let weight = filter.weight(x - (px as f64 + 0.5), y - (py as f64 + 0.5));
+                let sum = &mut self.sums[((py - area.top) * width + px - area.left) as usize];
+                *sum = (sum.0 + colour * weight, sum.1 + weight);
+            }
+        }
+    }
+
+    /// Add the splatted samples and the updated estimates to the render.
+    fn add_to(self, checkpoint: &mut Checkpoint, image_width: u32) {
+        let area = self.area;
+        let rows = self.sums.chunks((area.right - area.left) as usize);
+        for (y, row) in (area.top..area.bottom).zip(rows) {
+            let start = (y * image_width + area.left) as usize;
+            for (sum, &(colour, weight)) in checkpoint.sums[start..].iter_mut().zip(row) {
+                *sum = (sum.0 + colour, sum.1 + weight);
             }
         }
+
+        let tile = self.tile;
+        let rows = self.estimates.chunks((tile.right - tile.left) as usize);
+        for (y, row) in (tile.top..tile.bottom).zip(rows) {
+            let start = (y * image_width + tile.left) as usize;
+            checkpoint.estimates[start..start + row.len()].copy_from_slice(row);
+        }
+    }
+}
+
+/// The first surface seen through a pixel.
+#[derive(Clone, Copy, Debug)]
+struct Surface {
+    depth: f64,
+    normal: V3,
+    albedo: Colour,
+    material: Option<Material>,
+    object: Option<usize>,
+    // How many samples the pixel's colour got.
+    samples: u32,
+}
+
+impl Default for Surface {
+    fn default() -> Self {
+        Surface {
+            depth: f64::INFINITY,
+            normal: V3::zero(),
+            albedo: Colour::black(),
+            material: None,
+            object: None,
+            samples: 0,
+        }
+    }
+}
+
+/// Build a pass from the surfaces seen through each pixel.
+fn aov_pass(aov: Aov, surfaces: &[Surface]) -> Vec<Colour> {
+    let grey = |x: f64| Colour { r: x, g: x, b: x };
+    // Materials are numbered in the order they're first seen.
+    let mut materials: Vec<Material> = Vec::new();
+
+    surfaces
+        .iter()
+        .map(|s| match aov {
+            Aov::Depth => grey(s.depth),
+            Aov::Normal => Colour {
+                r: s.normal.x,
+                g: s.normal.y,
+                b: s.normal.z,
+            },
+            Aov::Albedo => s.albedo,
+            Aov::MaterialId => grey(s.material.map_or(0.0, |m| {
+                let id = match materials.iter().position(|&seen| seen == m) {
+                    Some(i) => i,
+                    None => {
+                        materials.push(m);
+                        materials.len() - 1
+                    }
+                };
+                (id + 1) as f64
+            })),
+            Aov::ObjectId => grey(s.object.map_or(0.0, |i| (i + 1) as f64)),
+            Aov::SampleCount => grey(s.samples as f64),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::point::Point;
+    use crate::shape::sphere::Sphere;
+
+    fn small_scene(progressive: Option<u32>) -> Scene {
+        let config = Config {
+            width: 8,
+            height: 6,
+            samples: 64,
+            progressive,
+            aovs: vec![Aov::Depth],
+            ..Config::default()
+        };
+        Scene::new().config(config).push(Shape::Sphere(Sphere::new(
+            Point::new(0.0, 0.0, -1.0),
+            0.5,
+            Material::lambertian(0.5, 0.5, 0.5),
+        )))
+    }
+
+    #[test]
+    fn cancelled_renders_give_each_pixel_one_sample() {
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        let mut passes = 0;
+        let image = small_scene(None).render_progressive(None, &cancel, &Silent, |_| passes += 1);
+        assert!(image.sample_counts().iter().all(|&count| count == 1));
+        assert_eq!(passes, 0);
     }
 
-    fn background(ray: Ray) -> Colour {
-        let unit = ray.direction().normalize();
-        let t = unit.y.abs();
-        Colour::linear_interpolation(Colour::new(1.0, 1.0, 1.0), Colour::new(0.5, 0.7, 1.0), t)
+    #[test]
+    fn cancelled_renders_pass_on_the_last_whole_pass() {
+        let cancel = CancelToken::new();
+        let mut passes = Vec::new();
+        let image = small_scene(Some(2)).render_progressive(None, &cancel, &Silent, |snapshot| {
+            passes.push((snapshot.checkpoint().samples(), snapshot.is_last()));
+            cancel.cancel();
+        });
+        assert_eq!(passes, vec![(2, false), (2, true)]);
+        assert!(image.sample_counts().iter().all(|&count| count == 2));
     }
 }
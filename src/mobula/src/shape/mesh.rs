@@ -0,0 +1,213 @@
+use serde::{Deserialize, Serialize};
+
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::bvh::Bvh;
+use crate::hit::{Hit, Hitable};
+use crate::material::Material;
+use crate::point::Point;
+use crate::ray::Ray;
+use crate::shape::triangle::Triangle;
+use crate::v3::V3;
+
+/// A triangle mesh.
+///
+/// The vertex buffers are shared by all the faces, which are triples of
+/// indices into them. Normals and texture coordinates are optional, but if
+/// they're there they need one entry per vertex. With normals the mesh is
+/// smooth shaded by interpolating them across each face.
+///
+/// Meshes are cheap to clone, since the buffers are shared.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "MeshDescription", into = "MeshDescription")]
+pub struct Mesh {
+    data: Arc<MeshData>,
+}
+
+#[derive(Debug)]
+struct MeshData {
+    positions: Vec<Point>,
+    normals: Vec<V3>,
+    uvs: Vec<(f64, f64)>,
+    material: Material,
+    faces: Bvh<[u32; 3]>,
+}
+
+impl Mesh {
+    pub fn new(
+        positions: Vec<Point>,
+        normals: Vec<V3>,
+        uvs: Vec<(f64, f64)>,
+        faces: Vec<[u32; 3]>,
+        material: Material,
+    ) -> Result<Self, String> {
+        if !normals.is_empty() && normals.len() != positions.len() {
+            return Err(format!(
+                "a mesh with {} positions has {} normals, they must match",
+                positions.len(),
+                normals.len()
+            ));
+        }
+        if !uvs.is_empty() && uvs.len() != positions.len() {
+            return Err(format!(
+                "a mesh with {} positions has {} uvs, they must match",
+                positions.len(),
+                uvs.len()
+            ));
+        }
+        if let Some(i) = faces
+            .iter()
+            .flatten()
+            .find(|&&i| i as usize >= positions.len())
+        {
+            return Err(format!(
+                "a mesh face uses vertex {}, but there are only {} vertices",
+                i,
+                positions.len()
+            ));
+        }
+
+        let bounds: Vec<Aabb> = faces
+            .iter()
+            .map(|&[i, j, k]| {
+                Aabb::empty()
+                    .grow(positions[i as usize])
+                    .grow(positions[j as usize])
+                    .grow(positions[k as usize])
+            })
+            .collect();
+
+        Ok(Mesh {
+            data: Arc::new(MeshData {
+                faces: Bvh::from_bounds(faces, &bounds),
+                positions,
+                normals,
+                uvs,
+                material,
+            }),
+        })
+    }
+
+    pub fn material(&self) -> Material {
+        self.data.material
+    }
+
+    /// The faces as flat triangles, ignoring any normals and texture
+    /// coordinates.
+    pub fn triangles(&self) -> impl Iterator<Item = Triangle> + '_ {
+        let data = &self.data;
+        data.faces.items().iter().map(move |&[i, j, k]| {
+            Triangle::new(
+                data.positions[i as usize],
+                data.positions[j as usize],
+                data.positions[k as usize],
+                data.material,
+            )
+        })
+    }
+}
+
+impl Hitable for Mesh {
+    fn is_hit_by(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        let data = &self.data;
+        data.faces
+            .traverse(ray, t_min, t_max, |_, &[i, j, k], closest_so_far| {
+                let (i, j, k) = (i as usize, j as usize, k as usize);
+                let (a, b, c) = (data.positions[i], data.positions[j], data.positions[k]);
+                let (t, u, v) = Triangle::intersect(a, b, c, ray, t_min, closest_so_far)?;
+                let w = 1.0 - u - v;
+
+                let geometric_normal = Triangle::normal(a, b, c);
+                let normal = if data.normals.is_empty() {
+                    geometric_normal
+                } else {
+                    (data.normals[i] * w + data.normals[j] * u + data.normals[k] * v).normalize()
+                };
+
+                let (s, t_coord) = if data.uvs.is_empty() {
+                    (u, v)
+                } else {
+                    let (ui, vi) = data.uvs[i];
+                    let (uj, vj) = data.uvs[j];
+                    let (uk, vk) = data.uvs[k];
+                    (ui * w + uj * u + uk * v, vi * w + vj * u + vk * v)
+                };
+
+                let hit = Hit::new(ray.at_parameter(t), normal, data.material, t);
+                Some(
+                    hit.with_uv(s, t_coord)
+                        .with_geometric_normal(geometric_normal),
+                )
+            })
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.data.faces.bounds()
+    }
+}
+
+/// How meshes are written in scene files.
+#[derive(Serialize, Deserialize)]
+struct MeshDescription {
+    positions: Vec<Point>,
+    #[serde(default)]
+    normals: Vec<V3>,
+    #[serde(default)]
+    uvs: Vec<(f64, f64)>,
+    faces: Vec<[u32; 3]>,
+    material: Material,
+}
+
+impl TryFrom<MeshDescription> for Mesh {
+    type Error = String;
+
+    fn try_from(d: MeshDescription) -> Result<Self, Self::Error> {
+        Mesh::new(d.positions, d.normals, d.uvs, d.faces, d.material)
+    }
+}
+
+impl From<Mesh> for MeshDescription {
+    fn from(mesh: Mesh) -> Self {
+        let data = &mesh.data;
+        MeshDescription {
+            positions: data.positions.clone(),
+            normals: data.normals.clone(),
+            uvs: data.uvs.clone(),
+            faces: data.faces.original_items(),
+            material: data.material,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_keeps_face_order() {
+        // A row of triangles, listed right to left so the BVH reorders them.
+        let n = 16;
+        let positions = (0..n)
+            .rev()
+            .flat_map(|i| {
+                let x = i as f64;
+                vec![
+                    Point::new(x, 0.0, 0.0),
+                    Point::new(x + 0.5, 0.0, 0.0),
+                    Point::new(x, 1.0, 0.0),
+                ]
+            })
+            .collect();
+        let faces: Vec<[u32; 3]> = (0..n).map(|i| [3 * i, 3 * i + 1, 3 * i + 2]).collect();
+        let material = Material::lambertian(0.5, 0.5, 0.5);
+        let mesh = Mesh::new(positions, Vec::new(), Vec::new(), faces.clone(), material).unwrap();
+        assert_ne!(mesh.data.faces.items(), &faces[..]);
+
+        let json = serde_json::to_string(&mesh).unwrap();
+        let read: Mesh = serde_json::from_str(&json).unwrap();
+        assert_eq!(MeshDescription::from(mesh).faces, faces);
+        assert_eq!(MeshDescription::from(read).faces, faces);
+    }
+}
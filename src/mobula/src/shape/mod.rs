@@ -1,23 +1,42 @@
 use serde::{Deserialize, Serialize};
 
+use crate::aabb::Aabb;
 use crate::hit::{Hit, Hitable};
 use crate::ray::Ray;
 
+pub mod mesh;
+pub mod obj;
 pub mod sphere;
+pub mod triangle;
 
 // This would be nicer as a trait, but the generic bounds for serde prevent
 // making a trait object of shapes.
-#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(tag = "type")]
 #[serde(rename_all = "snake_case")]
 pub enum Shape {
     Sphere(sphere::Sphere),
+    Triangle(triangle::Triangle),
+    Mesh(mesh::Mesh),
+    Obj(obj::Obj),
 }
 
 impl Hitable for Shape {
     fn is_hit_by(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
         match self {
             Shape::Sphere(s) => s.is_hit_by(ray, t_min, t_max),
+            Shape::Triangle(s) => s.is_hit_by(ray, t_min, t_max),
+            Shape::Mesh(s) => s.is_hit_by(ray, t_min, t_max),
+            Shape::Obj(s) => s.is_hit_by(ray, t_min, t_max),
+        }
+    }
+
+    fn bounds(&self) -> Aabb {
+        match self {
+            Shape::Sphere(s) => s.bounds(),
+            Shape::Triangle(s) => s.bounds(),
+            Shape::Mesh(s) => s.bounds(),
+            Shape::Obj(s) => s.bounds(),
         }
     }
 }
@@ -0,0 +1,507 @@
+//! Wavefront OBJ models, with materials optionally taken from MTL files.
+//!
+//! Only polygonal geometry is supported. Faces are triangulated as fans, and
+//! each group of faces which share an OBJ group and material becomes its own
+//! `Mesh`. Statements we don't use, like smoothing groups and lines, are
+//! ignored.
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::bvh::Bvh;
+use crate::hit::{Hit, Hitable};
+use crate::material::Material;
+use crate::point::Point;
+use crate::ray::Ray;
+use crate::shape::mesh::Mesh;
+use crate::v3::V3;
+
+/// A model loaded from an OBJ file.
+///
+/// In scene files the path is relative to the working directory, and any
+/// `mtllib` paths in the OBJ file are relative to the OBJ file.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "ObjDescription", into = "ObjDescription")]
+pub struct Obj {
+    description: ObjDescription,
+    meshes: Arc<Bvh<Mesh>>,
+}
+
+/// How OBJ models are written in scene files.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct ObjDescription {
+    path: PathBuf,
+    /// The material used for faces without one from an MTL file.
+    material: Material,
+    /// Use the materials from the MTL files the model references.
+    #[serde(default)]
+    mtl: bool,
+}
+
+impl Obj {
+    /// Load a model. Every face uses `material` unless `mtl` is set, in which
+    /// case faces after a `usemtl` statement use that MTL material instead.
+    pub fn load<P: AsRef<Path>>(path: P, material: Material, mtl: bool) -> Result<Self, ObjError> {
+        Obj::try_from(ObjDescription {
+            path: path.as_ref().to_owned(),
+            material,
+            mtl,
+        })
+    }
+
+    /// The meshes the model is made of.
+    pub fn meshes(&self) -> &[Mesh] {
+        self.meshes.items()
+    }
+}
+
+impl Hitable for Obj {
+    fn is_hit_by(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        self.meshes.is_hit_by(ray, t_min, t_max)
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.meshes.bounds()
+    }
+}
+
+impl TryFrom<ObjDescription> for Obj {
+    type Error = ObjError;
+
+    fn try_from(description: ObjDescription) -> Result<Self, Self::Error> {
+        let source = read(&description.path)?;
+        let mut parser = ObjParser::new(&description);
+        for (i, line) in source.lines().enumerate() {
+            parser.line = i + 1;
+            parser.statement(line)?;
+        }
+        let meshes = parser.finish()?;
+
+        Ok(Obj {
+            description,
+            meshes: Arc::new(Bvh::new(meshes)),
+        })
+    }
+}
+
+impl From<Obj> for ObjDescription {
+    fn from(obj: Obj) -> Self {
+        obj.description
+    }
+}
+
+/// Something wrong with an OBJ or MTL file.
+#[derive(Debug)]
+pub struct ObjError {
+    path: PathBuf,
+    line: Option<usize>,
+    message: String,
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.path.display(), line, self.message),
+            None => write!(f, "{}: {}", self.path.display(), self.message),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+fn read(path: &Path) -> Result<String, ObjError> {
+    std::fs::read_to_string(path).map_err(|e| ObjError {
+        path: path.to_owned(),
+        line: None,
+        message: e.to_string(),
+    })
+}
+
+/// Split a statement into its keyword and arguments, ignoring comments.
+fn tokens(line: &str) -> Option<(&str, Vec<&str>)> {
+    let line = line.split('#').next().unwrap_or("");
+    let mut words = line.split_whitespace();
+    let keyword = words.next()?;
+    Some((keyword, words.collect()))
+}
+
+/// Where we are in a file, for error messages.
+struct Location<'a> {
+    path: &'a Path,
+    line: usize,
+}
+
+impl<'a> Location<'a> {
+    fn error<T>(&self, message: String) -> Result<T, ObjError> {
+        Err(ObjError {
+            path: self.path.to_owned(),
+            line: Some(self.line),
+            message,
+        })
+    }
+
+    fn numbers(
+        &self,
+        keyword: &str,
+        args: &[&str],
+        min: usize,
+        max: usize,
+    ) -> Result<Vec<f64>, ObjError> {
+        if args.len() < min || args.len() > max {
+            let expected = if min == max {
+                format!("{}", min)
+            } else {
+                format!("{} to {}", min, max)
+            };
+            return self.error(format!(
+                "expected {} numbers after '{}', found {}",
+                expected,
+                keyword,
+                args.len()
+            ));
+        }
+        args.iter()
+            .map(|a| match a.parse() {
+                Ok(n) => Ok(n),
+                Err(_) => self.error(format!("'{}' is not a number", a)),
+            })
+            .collect()
+    }
+}
+
+/// The faces for one mesh, with the OBJ's separate position, texture and
+/// normal indices merged into a single index per vertex.
+struct MeshBuilder {
+    material: Material,
+    vertices: HashMap<(usize, Option<usize>, Option<usize>), u32>,
+    positions: Vec<Point>,
+    uvs: Vec<(f64, f64)>,
+    normals: Vec<V3>,
+    faces: Vec<[u32; 3]>,
+    // A mesh's normals and uvs are all-or-nothing, so we drop them if any
+    // vertex is missing one.
+    all_uvs: bool,
+    all_normals: bool,
+}
+
+impl MeshBuilder {
+    fn new(material: Material) -> Self {
+        MeshBuilder {
+            material,
+            vertices: HashMap::new(),
+            positions: Vec::new(),
+            uvs: Vec::new(),
+            normals: Vec::new(),
+            faces: Vec::new(),
+            all_uvs: true,
+            all_normals: true,
+        }
+    }
+
+    fn build(self) -> Result<Mesh, String> {
+        let uvs = if self.all_uvs { self.uvs } else { Vec::new() };
+        let normals = if self.all_normals {
+            self.normals
+        } else {
+            Vec::new()
+        };
+        Mesh::new(self.positions, normals, uvs, self.faces, self.material)
+    }
+}
+
+struct ObjParser<'a> {
+    path: &'a Path,
+    line: usize,
+    default_material: Material,
+    mtl: bool,
+
+    positions: Vec<Point>,
+    uvs: Vec<(f64, f64)>,
+    normals: Vec<V3>,
+    materials: HashMap<String, Material>,
+
+    current: MeshBuilder,
+    meshes: Vec<Mesh>,
+}
+
+impl<'a> ObjParser<'a> {
+    fn new(description: &'a ObjDescription) -> Self {
+        ObjParser {
+            path: &description.path,
+            line: 0,
+            default_material: description.material,
+            mtl: description.mtl,
+            positions: Vec::new(),
+            uvs: Vec::new(),
+            normals: Vec::new(),
+            materials: HashMap::new(),
+            current: MeshBuilder::new(description.material),
+            meshes: Vec::new(),
+        }
+    }
+
+    fn location(&self) -> Location<'_> {
+        Location {
+            path: self.path,
+            line: self.line,
+        }
+    }
+
+    fn statement(&mut self, line: &str) -> Result<(), ObjError> {
+        let (keyword, args) = match tokens(line) {
+            Some(t) => t,
+            None => return Ok(()),
+        };
+
+        match keyword {
+            "v" => {
+                // The optional fourth number is a weight for rational curves.
+                let n = self.location().numbers(keyword, &args, 3, 4)?;
+                self.positions.push(Point::new(n[0], n[1], n[2]));
+            }
+            "vt" => {
+                let n = self.location().numbers(keyword, &args, 1, 3)?;
+                self.uvs.push((n[0], n.get(1).cloned().unwrap_or(0.0)));
+            }
+            "vn" => {
+                let n = self.location().numbers(keyword, &args, 3, 3)?;
+                self.normals.push(V3::new(n[0], n[1], n[2]).normalize());
+            }
+            "f" => self.face(&args)?,
+            "g" | "o" => self.next_mesh(self.current.material)?,
+            "usemtl" if self.mtl => {
+                let name = args.join(" ");
+                let material = match self.materials.get(&name) {
+                    Some(&m) => m,
+                    None => {
+                        return self
+                            .location()
+                            .error(format!("no material named '{}' in the mtllib files", name));
+                    }
+                };
+                self.next_mesh(material)?;
+            }
+            "mtllib" if self.mtl => {
+                let directory = self.path.parent().unwrap_or_else(|| Path::new(""));
+                for file in args {
+                    let materials = parse_mtl(&directory.join(file))?;
+                    self.materials.extend(materials);
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Finish the current mesh, and start a new one using `material`.
+    fn next_mesh(&mut self, material: Material) -> Result<(), ObjError> {
+        let next = MeshBuilder::new(material);
+        let current = std::mem::replace(&mut self.current, next);
+        if !current.faces.is_empty() {
+            match current.build() {
+                Ok(mesh) => self.meshes.push(mesh),
+                Err(message) => return self.location().error(message),
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Vec<Mesh>, ObjError> {
+        self.next_mesh(self.default_material)?;
+        if self.meshes.is_empty() {
+            return Err(ObjError {
+                path: self.path.to_owned(),
+                line: None,
+                message: "there are no faces".into(),
+            });
+        }
+        Ok(self.meshes)
+    }
+
+    /// Turn an OBJ index, which start at 1 and can be negative to count back
+    /// from the most recent, into an index into a buffer of `len` items.
+    fn index(&self, word: &str, kind: &str, len: usize) -> Result<usize, ObjError> {
+        let i: isize = match word.parse() {
+            Ok(i) => i,
+            Err(_) => {
+                return self
+                    .location()
+                    .error(format!("'{}' is not a {} index", word, kind))
+            }
+        };
+        let resolved = if i > 0 { i - 1 } else { len as isize + i };
+        if i == 0 || resolved < 0 || resolved >= len as isize {
+            return self.location().error(format!(
+                "{} index {} is out of range, there are {} so far",
+                kind, i, len
+            ));
+        }
+        Ok(resolved as usize)
+    }
+
+    fn face(&mut self, args: &[&str]) -> Result<(), ObjError> {
+        if args.len() < 3 {
+            return self.location().error(format!(
+                "a face needs at least 3 vertices, found {}",
+                args.len()
+            ));
+        }
+
+        let mut vertices = Vec::with_capacity(args.len());
+        for vertex in args {
+            let mut parts = vertex.split('/');
+            let position =
+                self.index(parts.next().unwrap_or(""), "vertex", self.positions.len())?;
+            let uv = match parts.next() {
+                None | Some("") => None,
+                Some(word) => Some(self.index(word, "texture", self.uvs.len())?),
+            };
+            let normal = match parts.next() {
+                None | Some("") => None,
+                Some(word) => Some(self.index(word, "normal", self.normals.len())?),
+            };
+            vertices.push(self.vertex(position, uv, normal));
+        }
+
+        for i in 1..vertices.len() - 1 {
+            self.current
+                .faces
+                .push([vertices[0], vertices[i], vertices[i + 1]]);
+        }
+        Ok(())
+    }
+
+    /// The index in the current mesh of a vertex, adding it if it's new.
+    fn vertex(&mut self, position: usize, uv: Option<usize>, normal: Option<usize>) -> u32 {
+        let (uvs, normals) = (&self.uvs, &self.normals);
+        let mesh = &mut self.current;
+        let key = (position, uv, normal);
+        if let Some(&index) = mesh.vertices.get(&key) {
+            return index;
+        }
+
+        let index = mesh.positions.len() as u32;
+        mesh.positions.push(self.positions[position]);
+        mesh.uvs.push(uv.map(|i| uvs[i]).unwrap_or((0.0, 0.0)));
+        mesh.normals
+            .push(normal.map(|i| normals[i]).unwrap_or_default());
+        mesh.all_uvs &= uv.is_some();
+        mesh.all_normals &= normal.is_some();
+        mesh.vertices.insert(key, index);
+        index
+    }
+}
+
+/// The parts of an MTL material we can use.
+struct MtlMaterial {
+    diffuse: [f64; 3],
+    specular: [f64; 3],
+    emission: [f64; 3],
+    shininess: f64,
+    refractive_index: f64,
+    opacity: f64,
+    illumination: u32,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        MtlMaterial {
+            diffuse: [0.8, 0.8, 0.8],
+            specular: [0.0, 0.0, 0.0],
+            emission: [0.0, 0.0, 0.0],
+            shininess: 0.0,
+            refractive_index: 1.5,
+            opacity: 1.0,
+            illumination: 2,
+        }
+    }
+}
+
+impl MtlMaterial {
+    /// Pick the one of our materials closest to what the MTL describes.
+    fn to_material(&self) -> Material {
+        let [r, g, b] = self.specular;
+        let is_glass = self.opacity < 1.0 || [4, 6, 7, 9].contains(&self.illumination);
+        let is_mirror = self.illumination == 3 || (self.diffuse == [0.0; 3] && r + g + b > 0.0);
+
+        // Emission can be brighter than white, so we split it into a colour
+        // and a strength.
+        let strength = self.emission.iter().cloned().fold(0.0, f64::max);
+
+        if strength > 0.0 {
+            let [r, g, b] = self.emission;
+            Material::diffuse_light(r / strength, g / strength, b / strength, strength)
+        } else if is_glass {
+            Material::dialectric(self.refractive_index)
+        } else if is_mirror {
+            // A rough conversion from a Phong exponent to our fuzziness.
+            let fuzz = (2.0 / (self.shininess + 2.0)).sqrt();
+            Material::metal(r, g, b, fuzz)
+        } else {
+            let [r, g, b] = self.diffuse;
+            Material::lambertian(r, g, b)
+        }
+    }
+}
+
+fn parse_mtl(path: &Path) -> Result<HashMap<String, Material>, ObjError> {
+    let source = read(path)?;
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlMaterial)> = None;
+
+    for (i, line) in source.lines().enumerate() {
+        let location = Location { path, line: i + 1 };
+        let (keyword, args) = match tokens(line) {
+            Some(t) => t,
+            None => continue,
+        };
+
+        if keyword == "newmtl" {
+            if let Some((name, m)) = current.take() {
+                materials.insert(name, m.to_material());
+            }
+            current = Some((args.join(" "), MtlMaterial::default()));
+            continue;
+        }
+
+        let m = match current.as_mut() {
+            Some((_, m)) => m,
+            None if ["Kd", "Ks", "Ke", "Ns", "Ni", "d", "Tr", "illum"].contains(&keyword) => {
+                return location.error(format!("'{}' before any 'newmtl'", keyword));
+            }
+            None => continue,
+        };
+
+        let colour = |args: &[&str], max: f64| -> Result<[f64; 3], ObjError> {
+            let n = location.numbers(keyword, args, 1, 3)?;
+            // A single number is a grey.
+            let c = |i: usize| n.get(i).cloned().unwrap_or(n[0]).clamp(0.0, max);
+            Ok([c(0), c(1), c(2)])
+        };
+
+        match keyword {
+            "Kd" => m.diffuse = colour(&args, 1.0)?,
+            "Ks" => m.specular = colour(&args, 1.0)?,
+            "Ke" => m.emission = colour(&args, f64::INFINITY)?,
+            "Ns" => m.shininess = location.numbers(keyword, &args, 1, 1)?[0],
+            "Ni" => m.refractive_index = location.numbers(keyword, &args, 1, 1)?[0],
+            "d" => m.opacity = location.numbers(keyword, &args, 1, 1)?[0],
+            "Tr" => m.opacity = 1.0 - location.numbers(keyword, &args, 1, 1)?[0],
+            "illum" => m.illumination = location.numbers(keyword, &args, 1, 1)?[0] as u32,
+            _ => {}
+        }
+    }
+
+    if let Some((name, m)) = current {
+        materials.insert(name, m.to_material());
+    }
+
+    Ok(materials)
+}
@@ -1,9 +1,11 @@
 use serde::{Deserialize, Serialize};
 
+use crate::aabb::Aabb;
 use crate::hit::{Hit, Hitable};
 use crate::material::Material;
 use crate::point::Point;
 use crate::ray::Ray;
+use crate::v3::V3;
 
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct Sphere {
@@ -51,4 +53,33 @@ impl Hitable for Sphere {
             None
         }
     }
+
+    fn bounds(&self) -> Aabb {
+        // A negative radius turns the sphere inside out, but it's the same size.
+        let radius = self.radius.abs();
+        let r = V3::new(radius, radius, radius);
+        Aabb::new(self.centre - r, self.centre + r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bvh::Bvh;
+
+    #[test]
+    fn negative_radius_is_found_through_a_bvh() {
+        let material = Material::lambertian(0.5, 0.5, 0.5);
+        let spheres = vec![
+            Sphere::new(Point::new(-2.0, 0.0, 0.0), -0.5, material),
+            Sphere::new(Point::new(2.0, 0.0, 0.0), 0.5, material),
+        ];
+        let bvh = Bvh::new(spheres);
+
+        let ray = Ray::new(Point::new(-2.0, 0.0, 5.0), V3::new(0.0, 0.0, -1.0));
+        let hit = bvh
+            .is_hit_by(&ray, 0.001, f64::MAX)
+            .expect("missed the sphere");
+        assert!((hit.t - 4.5).abs() < 1e-9);
+    }
 }
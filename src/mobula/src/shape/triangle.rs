@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+
+use crate::aabb::Aabb;
+use crate::hit::{Hit, Hitable};
+use crate::material::Material;
+use crate::point::Point;
+use crate::ray::Ray;
+use crate::v3::V3;
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct Triangle {
+    pub a: Point,
+    pub b: Point,
+    pub c: Point,
+    pub material: Material,
+}
+
+impl Triangle {
+    pub fn new(a: Point, b: Point, c: Point, material: Material) -> Self {
+        Triangle { a, b, c, material }
+    }
+
+    /// The normal of the triangle, facing the side the vertices wind
+    /// counter-clockwise around.
+    pub fn normal(a: Point, b: Point, c: Point) -> V3 {
+        (b - a).cross(c - a).normalize()
+    }
+
+    /// The Möller–Trumbore ray-triangle intersection.
+    ///
+    /// On a hit this returns the ray parameter and the barycentric coordinates
+    /// of the intersection with respect to `b` and `c`.
+    pub fn intersect(
+        a: Point,
+        b: Point,
+        c: Point,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<(f64, f64, f64)> {
+        let edge1 = b - a;
+        let edge2 = c - a;
+        let p = ray.direction().cross(edge2);
+        let determinant = edge1.dot(p);
+
+        // The ray is parallel to the triangle.
+        if determinant.abs() < f64::EPSILON {
+            return None;
+        }
+        let inverse_determinant = 1.0 / determinant;
+
+        let s = ray.origin() - a;
+        let u = s.dot(p) * inverse_determinant;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let q = s.cross(edge1);
+        let v = ray.direction().dot(q) * inverse_determinant;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let t = edge2.dot(q) * inverse_determinant;
+        if t < t_max && t > t_min {
+            Some((t, u, v))
+        } else {
+            None
+        }
+    }
+}
+
+impl Hitable for Triangle {
+    fn is_hit_by(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        let (t, u, v) = Triangle::intersect(self.a, self.b, self.c, ray, t_min, t_max)?;
+        let normal = Triangle::normal(self.a, self.b, self.c);
+        Some(Hit::new(ray.at_parameter(t), normal, self.material, t).with_uv(u, v))
+    }
+
+    fn bounds(&self) -> Aabb {
+        Aabb::empty().grow(self.a).grow(self.b).grow(self.c)
+    }
+}
@@ -0,0 +1,248 @@
+//! An analytic daylight sky, following Preetham, Shirley and Smits, "A
+//! Practical Analytic Model for Daylight" (1999).
+//!
+//! The sky's brightness and colour come from fits to measured skies, driven
+//! by where the sun is and how hazy the air is. The sun itself isn't part of
+//! the sky. It's a directional light of the matching colour, so it can be
+//! sampled with shadow rays like any other light.
+
+use serde::{Deserialize, Serialize};
+
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::colour::Colour;
+use crate::light::Light;
+use crate::v3::V3;
+
+/// The sun's angular radius seen from the earth, in degrees.
+const SUN_ANGULAR_RADIUS: f64 = 0.2665;
+
+/// The sun's illuminance above the atmosphere, in kilolux.
+const SOLAR_ILLUMINANCE: f64 = 128.0;
+
+/// The model works in kilocandelas per square metre. This brings a white
+/// surface in full sun to about 1.
+const SCALE: f64 = 0.03;
+
+/// A daylight sky, with +y up.
+///
+/// The sun is `elevation` degrees above the horizon, and `azimuth` degrees
+/// round from the -z direction towards +x. `turbidity` is how hazy the air
+/// is, from about 2 for a very clear day to 10 for a hazy one.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(from = "SkyDescription", into = "SkyDescription")]
+pub struct Sky {
+    description: SkyDescription,
+    model: Arc<Model>,
+}
+
+/// Everything worked out from the description.
+#[derive(Debug)]
+struct Model {
+    // Towards the sun, as a unit vector.
+    sun_direction: V3,
+    // The sun's angle from the zenith, in radians.
+    sun_theta: f64,
+    // The sky's luminance and chromaticity straight up.
+    zenith: [f64; 3],
+    // The Perez distribution's coefficients for the luminance and each
+    // chromaticity coordinate.
+    perez: [[f64; 5]; 3],
+    sun: Option<Light>,
+}
+
+/// How skies are written in scene files.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+struct SkyDescription {
+    #[serde(default = "SkyDescription::default_elevation")]
+    elevation: f64,
+    #[serde(default)]
+    azimuth: f64,
+    #[serde(default = "SkyDescription::default_turbidity")]
+    turbidity: f64,
+    /// Scales the light from both the sky and the sun.
+    #[serde(default = "SkyDescription::default_intensity")]
+    intensity: f64,
+    /// Light the scene with the sun as well as the sky.
+    #[serde(default = "SkyDescription::default_sun")]
+    sun: bool,
+}
+
+impl SkyDescription {
+    fn default_elevation() -> f64 {
+        45.0
+    }
+
+    fn default_turbidity() -> f64 {
+        3.0
+    }
+
+    fn default_intensity() -> f64 {
+        1.0
+    }
+
+    fn default_sun() -> bool {
+        true
+    }
+}
+
+impl Sky {
+    pub fn new(elevation: f64, azimuth: f64, turbidity: f64) -> Self {
+        Sky::from(SkyDescription {
+            elevation,
+            azimuth,
+            turbidity,
+            intensity: SkyDescription::default_intensity(),
+            sun: SkyDescription::default_sun(),
+        })
+    }
+
+    /// The light coming from a direction, not counting the sun. Below the
+    /// horizon the sky just carries on as it is at the horizon.
+    pub fn radiance(&self, direction: V3) -> Colour {
+        let direction = direction.normalize();
+        // Just above the horizon, where the model still holds.
+        let cos_theta = direction.y.max(0.01);
+        let cos_gamma = direction.dot(self.model.sun_direction).clamp(-1.0, 1.0);
+        let gamma = cos_gamma.acos();
+
+        let model = &self.model;
+        // The zenith values are for straight up, which is the sun's angle
+        // from the zenith away from the sun.
+        let value = |i: usize| {
+            model.zenith[i] * perez(model.perez[i], cos_theta, gamma)
+                / perez(model.perez[i], 1.0, model.sun_theta)
+        };
+        let (luminance, x, y) = (value(0), value(1), value(2));
+
+        xyy_to_rgb(x, y, luminance) * (SCALE * self.description.intensity)
+    }
+
+    /// The sun, as a directional light, unless it's been left out or is
+    /// below the horizon.
+    pub fn sun(&self) -> Option<Light> {
+        self.model.sun
+    }
+}
+
+impl From<SkyDescription> for Sky {
+    fn from(description: SkyDescription) -> Self {
+        // The model doesn't hold for the sun below the horizon.
+        let elevation = description.elevation.clamp(0.0, 90.0).to_radians();
+        let azimuth = description.azimuth.to_radians();
+        let turbidity = description.turbidity.clamp(1.7, 10.0);
+
+        let sun_direction = V3::new(
+            elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+            -elevation.cos() * azimuth.cos(),
+        );
+        let theta = PI / 2.0 - elevation;
+        let t = turbidity;
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta);
+        let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let (theta2, theta3) = (theta * theta, theta * theta * theta);
+        let zenith_x = t * t * (0.00166 * theta3 - 0.00375 * theta2 + 0.00209 * theta)
+            + t * (-0.02903 * theta3 + 0.06377 * theta2 - 0.03202 * theta + 0.00394)
+            + (0.11693 * theta3 - 0.21196 * theta2 + 0.06052 * theta + 0.25886);
+        let zenith_y = t * t * (0.00275 * theta3 - 0.00610 * theta2 + 0.00317 * theta)
+            + t * (-0.04214 * theta3 + 0.08970 * theta2 - 0.04153 * theta + 0.00516)
+            + (0.15346 * theta3 - 0.26756 * theta2 + 0.06670 * theta + 0.26688);
+
+        let perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+
+        let sun = if description.sun && description.elevation > 0.0 {
+            Some(Light::Directional {
+                direction: -sun_direction,
+                colour: sun_colour(theta, turbidity),
+                intensity: SOLAR_ILLUMINANCE * SCALE * description.intensity,
+                angular_radius: SUN_ANGULAR_RADIUS,
+            })
+        } else {
+            None
+        };
+
+        Sky {
+            description,
+            model: Arc::new(Model {
+                sun_direction,
+                sun_theta: theta,
+                zenith: [zenith_luminance.max(0.0), zenith_x, zenith_y],
+                perez,
+                sun,
+            }),
+        }
+    }
+}
+
+impl From<Sky> for SkyDescription {
+    fn from(sky: Sky) -> Self {
+        sky.description
+    }
+}
+
+/// The Perez sky distribution, for a direction at an angle with cosine
+/// `cos_theta` from the zenith, and `gamma` radians from the sun.
+fn perez([a, b, c, d, e]: [f64; 5], cos_theta: f64, gamma: f64) -> f64 {
+    let cos_gamma = gamma.cos();
+    (1.0 + a * (b / cos_theta.max(0.01)).exp())
+        * (1.0 + c * (d * gamma).exp() + e * cos_gamma * cos_gamma)
+}
+
+/// Convert a CIE xyY colour to linear sRGB.
+fn xyy_to_rgb(x: f64, y: f64, luminance: f64) -> Colour {
+    if y <= 0.0 {
+        return Colour::black();
+    }
+    let big_x = x / y * luminance;
+    let big_z = (1.0 - x - y) / y * luminance;
+    let r = 3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z;
+    let g = -0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z;
+    let b = 0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z;
+    Colour::new(r.max(0.0), g.max(0.0), b.max(0.0))
+}
+
+/// How much of the sun's light gets through the atmosphere to the ground,
+/// for red, green and blue, when it's `theta` from the zenith. This uses
+/// the paper's Rayleigh and aerosol scattering, but not absorption by ozone
+/// and water vapour.
+fn sun_colour(theta: f64, turbidity: f64) -> Colour {
+    // How much air the light goes through, relative to straight down.
+    let mass = 1.0 / (theta.cos() + 0.15 * (93.885 - theta.to_degrees()).powf(-1.253));
+    let beta = 0.04608 * turbidity - 0.04586;
+    let transmittance = |wavelength: f64| {
+        let rayleigh = (-0.008735 * mass * wavelength.powf(-4.08)).exp();
+        let aerosol = (-beta * mass * wavelength.powf(-1.3)).exp();
+        rayleigh * aerosol
+    };
+    // Wavelengths in micrometres.
+    Colour::new(
+        transmittance(0.680),
+        transmittance(0.550),
+        transmittance(0.440),
+    )
+}
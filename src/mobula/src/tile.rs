@@ -0,0 +1,256 @@
+//! Splitting the image into square tiles, and the order to render them in.
+//!
+//! Neighbouring pixels usually hit the same objects, so rendering a small
+//! square at a time keeps the parts of the scene a thread is using in its
+//! cache, where a whole row would keep moving on to new ones.
+
+use serde::{Deserialize, Serialize};
+
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+/// The order tiles are started in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TileOrder {
+    /// Row by row, from the top left.
+    Scanline,
+    /// Outwards from the middle of the image, where the subject usually is,
+    /// so it shows up first in snapshots.
+    Spiral,
+    /// Along a Hilbert curve, so each tile is next to the one before it.
+    #[default]
+    Hilbert,
+}
+
+impl TileOrder {
+    pub const NAMES: [&'static str; 3] = ["scanline", "spiral", "hilbert"];
+}
+
+impl FromStr for TileOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "scanline" => Ok(TileOrder::Scanline),
+            "spiral" => Ok(TileOrder::Spiral),
+            "hilbert" => Ok(TileOrder::Hilbert),
+            _ => Err(format!("unknown tile order '{}'", s)),
+        }
+    }
+}
+
+/// A rectangle of pixels. Tiles on the right and bottom edges of the image
+/// can be smaller than the rest.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tile {
+    pub left: u32,
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+}
+
+impl Tile {
+    /// The whole of an image.
+    pub fn image(width: u32, height: u32) -> Self {
+        Tile {
+            left: 0,
+            top: 0,
+            right: width,
+            bottom: height,
+        }
+    }
+
+    pub fn width(self) -> u32 {
+        self.right.saturating_sub(self.left)
+    }
+
+    pub fn height(self) -> u32 {
+        self.bottom.saturating_sub(self.top)
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.width() == 0 || self.height() == 0
+    }
+
+    /// The pixels in both.
+    pub fn intersection(self, other: Tile) -> Tile {
+        Tile {
+            left: self.left.max(other.left),
+            top: self.top.max(other.top),
+            right: self.right.min(other.right),
+            bottom: self.bottom.min(other.bottom),
+        }
+    }
+
+    /// The smallest tile covering both.
+    pub fn union(self, other: Tile) -> Tile {
+        if self.is_empty() {
+            return other;
+        }
+        if other.is_empty() {
+            return self;
+        }
+        Tile {
+            left: self.left.min(other.left),
+            top: self.top.min(other.top),
+            right: self.right.max(other.right),
+            bottom: self.bottom.max(other.bottom),
+        }
+    }
+
+    /// The tile grown by `by` pixels on every side.
+    pub fn grow(self, by: u32) -> Tile {
+        Tile {
+            left: self.left.saturating_sub(by),
+            top: self.top.saturating_sub(by),
+            right: self.right.saturating_add(by),
+            bottom: self.bottom.saturating_add(by),
+        }
+    }
+}
+
+/// Cover a region of an image with tiles `size` pixels across, in `order`.
+pub fn tiles(region: Tile, size: u32, order: TileOrder) -> Vec<Tile> {
+    let size = size.max(1);
+    let across = region.width().div_ceil(size);
+    let down = region.height().div_ceil(size);
+    if across == 0 || down == 0 {
+        return Vec::new();
+    }
+
+    let mut grid: Vec<(u32, u32)> = (0..down)
+        .flat_map(|y| (0..across).map(move |x| (x, y)))
+        .collect();
+
+    match order {
+        TileOrder::Scanline => {}
+        TileOrder::Spiral => {
+            let centre_x = f64::from(across - 1) / 2.0;
+            let centre_y = f64::from(down - 1) / 2.0;
+            // Ring by ring, going round each one clockwise.
+            let key = |&(x, y): &(u32, u32)| {
+                let dx = f64::from(x) - centre_x;
+                let dy = f64::from(y) - centre_y;
+                (dx.abs().max(dy.abs()), dy.atan2(dx))
+            };
+            grid.sort_by(|a, b| key(a).partial_cmp(&key(b)).unwrap_or(Ordering::Equal));
+        }
+        TileOrder::Hilbert => {
+            let side = across.max(down).next_power_of_two();
+            grid.sort_by_key(|&(x, y)| hilbert_index(side, x, y));
+        }
+    }
+
+    grid.into_iter()
+        .map(|(x, y)| Tile {
+            left: region.left + x * size,
+            top: region.top + y * size,
+            right: (region.left + (x + 1) * size).min(region.right),
+            bottom: (region.top + (y + 1) * size).min(region.bottom),
+        })
+        .collect()
+}
+
+/// How far along a Hilbert curve filling a `side` by `side` square the cell
+/// (`x`, `y`) is. `side` has to be a power of two.
+fn hilbert_index(side: u32, mut x: u32, mut y: u32) -> u64 {
+    let mut index = 0;
+    let mut s = side / 2;
+    while s > 0 {
+        let rx = u32::from(x & s > 0);
+        let ry = u32::from(y & s > 0);
+        index += u64::from(s) * u64::from(s) * u64::from((3 * rx) ^ ry);
+        // Rotate the quadrant so the curve inside it joins up with the rest.
+        if ry == 0 {
+            if rx == 1 {
+                x = side - 1 - x;
+                y = side - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles_cover_each_pixel_once() {
+        let regions = [
+            Tile::image(100, 70),
+            Tile::image(32, 32),
+            Tile::image(1, 1),
+            Tile {
+                left: 13,
+                top: 7,
+                right: 90,
+                bottom: 41,
+            },
+        ];
+        let orders = [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert];
+        for &region in &regions {
+            for &order in &orders {
+                for &size in &[1, 7, 16, 32, 200] {
+                    let mut covered = vec![0; 100 * 70];
+                    for tile in tiles(region, size, order) {
+                        assert!(!tile.is_empty());
+                        for y in tile.top..tile.bottom {
+                            for x in tile.left..tile.right {
+                                covered[(y * 100 + x) as usize] += 1;
+                            }
+                        }
+                    }
+                    for y in 0..70 {
+                        for x in 0..100 {
+                            let inside = x >= region.left
+                                && x < region.right
+                                && y >= region.top
+                                && y < region.bottom;
+                            let expected = if inside { 1 } else { 0 };
+                            assert_eq!(
+                                covered[(y * 100 + x) as usize],
+                                expected,
+                                "pixel ({}, {}) of {:?} in {} tiles {:?}",
+                                x,
+                                y,
+                                region,
+                                size,
+                                order
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn empty_regions_have_no_tiles() {
+        assert!(tiles(Tile::image(0, 10), 16, TileOrder::Hilbert).is_empty());
+        assert!(tiles(Tile::image(10, 0), 16, TileOrder::Spiral).is_empty());
+    }
+
+    #[test]
+    fn hilbert_curve_steps_to_neighbours() {
+        for &side in &[1, 2, 4, 8, 32] {
+            let mut cells = vec![None; (side * side) as usize];
+            for y in 0..side {
+                for x in 0..side {
+                    let index = hilbert_index(side, x, y) as usize;
+                    assert!(cells[index].is_none(), "two cells at {}", index);
+                    cells[index] = Some((x, y));
+                }
+            }
+
+            let cells: Vec<(u32, u32)> = cells.into_iter().map(Option::unwrap).collect();
+            for pair in cells.windows(2) {
+                let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+                assert_eq!(x0.abs_diff(x1) + y0.abs_diff(y1), 1);
+            }
+        }
+    }
+}
@@ -0,0 +1,305 @@
+//! Turning the light that reaches the camera into colours a display can show.
+//!
+//! Rendered colours are scaled by the exposure, squeezed into [0, 1] by a
+//! tone mapping operator, and then encoded with a transfer function.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use std::str::FromStr;
+
+use crate::colour::Colour;
+use crate::named::{self, Named};
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[serde(remote = "Self", tag = "type")]
+#[serde(rename_all = "snake_case")]
+pub enum ToneMap {
+    /// Anything brighter than white is clipped.
+    #[default]
+    Clamp,
+    /// Reinhard's operator, which compresses highlights but never quite
+    /// reaches white.
+    Reinhard,
+    /// Reinhard's operator, adjusted so light as bright as `white` (and
+    /// anything brighter) is white.
+    ExtendedReinhard {
+        #[serde(default = "ToneMap::default_white")]
+        white: f64,
+    },
+    /// Stephen Hill's fit of the ACES filmic curve.
+    Aces,
+    /// An approximation of Troy Sobotka's AgX, which desaturates highlights
+    /// rather than skewing their hue.
+    Agx,
+}
+
+/// How colours are encoded in the output image.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[serde(remote = "Self", tag = "type")]
+#[serde(rename_all = "snake_case")]
+pub enum Transfer {
+    /// The piecewise sRGB curve, which is what most displays expect.
+    #[default]
+    Srgb,
+    /// A pure power curve.
+    Gamma {
+        #[serde(default = "Transfer::default_gamma")]
+        gamma: f64,
+    },
+    /// No encoding at all.
+    Linear,
+}
+
+impl ToneMap {
+    pub const NAMES: [&'static str; 5] = ["clamp", "reinhard", "extended_reinhard", "aces", "agx"];
+
+    fn default_white() -> f64 {
+        4.0
+    }
+
+    pub fn apply(self, c: Colour) -> Colour {
+        match self {
+            ToneMap::Clamp => c.clamp(),
+            ToneMap::Reinhard => scale_luminance(c, |l| l / (1.0 + l)),
+            ToneMap::ExtendedReinhard { white } => {
+                scale_luminance(c, |l| l * (1.0 + l / (white * white)) / (1.0 + l))
+            }
+            ToneMap::Aces => aces(c),
+            ToneMap::Agx => agx(c),
+        }
+    }
+}
+
+impl Transfer {
+    pub const NAMES: [&'static str; 3] = ["srgb", "gamma", "linear"];
+
+    fn default_gamma() -> f64 {
+        2.2
+    }
+
+    pub fn encode(self, c: Colour) -> Colour {
+        let f = |x: f64| match self {
+            Transfer::Srgb if x <= 0.003_130_8 => 12.92 * x,
+            Transfer::Srgb => 1.055 * x.powf(1.0 / 2.4) - 0.055,
+            Transfer::Gamma { gamma } => x.powf(1.0 / gamma),
+            Transfer::Linear => x,
+        };
+        Colour::new(f(c.r), f(c.g), f(c.b))
+    }
+}
+
+impl FromStr for ToneMap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "clamp" => Ok(ToneMap::Clamp),
+            "reinhard" => Ok(ToneMap::Reinhard),
+            "extended_reinhard" => Ok(ToneMap::ExtendedReinhard {
+                white: ToneMap::default_white(),
+            }),
+            "aces" => Ok(ToneMap::Aces),
+            "agx" => Ok(ToneMap::Agx),
+            _ => Err(format!("unknown tone map '{}'", s)),
+        }
+    }
+}
+
+impl FromStr for Transfer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "srgb" => Ok(Transfer::Srgb),
+            "gamma" => Ok(Transfer::Gamma {
+                gamma: Transfer::default_gamma(),
+            }),
+            "linear" => Ok(Transfer::Linear),
+            _ => Err(format!("unknown transfer function '{}'", s)),
+        }
+    }
+}
+
+impl Named for ToneMap {
+    const WHAT: &'static str = "tone map";
+    const NAMES: &'static [&'static str] = &ToneMap::NAMES;
+
+    fn read_tagged<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        ToneMap::deserialize(deserializer)
+    }
+}
+
+impl Serialize for ToneMap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ToneMap::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ToneMap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        named::read(deserializer)
+    }
+}
+
+impl Named for Transfer {
+    const WHAT: &'static str = "transfer function";
+    const NAMES: &'static [&'static str] = &Transfer::NAMES;
+
+    fn read_tagged<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Transfer::deserialize(deserializer)
+    }
+}
+
+impl Serialize for Transfer {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Transfer::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Transfer {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        named::read(deserializer)
+    }
+}
+
+/// Scale a colour so its luminance is mapped through `f`, which keeps the hue
+/// unlike mapping each channel separately.
+fn scale_luminance<F: Fn(f64) -> f64>(c: Colour, f: F) -> Colour {
+    let l = c.luminance();
+    if l <= 0.0 {
+        Colour::black()
+    } else {
+        (c * (f(l) / l)).clamp()
+    }
+}
+
+/// Multiply a colour by a matrix, written in rows.
+fn transform(m: [[f64; 3]; 3], c: Colour) -> Colour {
+    let row = |r: [f64; 3]| r[0] * c.r + r[1] * c.g + r[2] * c.b;
+    Colour {
+        r: row(m[0]),
+        g: row(m[1]),
+        b: row(m[2]),
+    }
+}
+
+fn aces(c: Colour) -> Colour {
+    // From linear sRGB into the space the curve was fit in, and back.
+    const INPUT: [[f64; 3]; 3] = [
+        [0.59719, 0.35458, 0.04823],
+        [0.07600, 0.90834, 0.01566],
+        [0.02840, 0.13383, 0.83777],
+    ];
+    const OUTPUT: [[f64; 3]; 3] = [
+        [1.60475, -0.53108, -0.07367],
+        [-0.10208, 1.10813, -0.00605],
+        [-0.00327, -0.07276, 1.07602],
+    ];
+
+    let fit = |v: f64| {
+        (v * (v + 0.024_578_6) - 0.000_090_537) / (v * (0.983_729 * v + 0.432_951) + 0.238_081)
+    };
+
+    let c = transform(INPUT, c);
+    let c = Colour {
+        r: fit(c.r),
+        g: fit(c.g),
+        b: fit(c.b),
+    };
+    transform(OUTPUT, c).clamp()
+}
+
+fn agx(c: Colour) -> Colour {
+    // Squeeze colours in towards white before the curve, and back out after.
+    const INSET: [[f64; 3]; 3] = [
+        [
+            0.842_479_062_253_094,
+            0.078_433_599_999_999_2,
+            0.079_223_745_147_764_3,
+        ],
+        [
+            0.042_328_242_261_012_3,
+            0.878_468_636_469_772,
+            0.079_166_127_460_543_4,
+        ],
+        [0.042_375_654_905_705_1, 0.078_433_6, 0.879_142_973_793_104],
+    ];
+    const OUTSET: [[f64; 3]; 3] = [
+        [
+            1.196_879_005_120_17,
+            -0.098_020_881_140_136_8,
+            -0.099_029_744_079_720_5,
+        ],
+        [
+            -0.052_896_851_757_456_2,
+            1.151_903_129_904_17,
+            -0.098_961_176_844_843_3,
+        ],
+        [
+            -0.052_971_635_514_443_8,
+            -0.098_043_450_117_124_1,
+            1.151_073_672_641_16,
+        ],
+    ];
+    // The curve covers from 10 stops below middle grey to 6.5 stops above.
+    const MIN_EV: f64 = -12.473_931_188;
+    const MAX_EV: f64 = 4.026_068_812;
+
+    let curve = |x: f64| {
+        let encoded = (x.max(1e-10).log2().clamp(MIN_EV, MAX_EV) - MIN_EV) / (MAX_EV - MIN_EV);
+        // A polynomial fit of AgX's default contrast sigmoid.
+        let x = encoded;
+        let x2 = x * x;
+        let x4 = x2 * x2;
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+            - 0.00232
+    };
+
+    let c = transform(INSET, c);
+    let c = Colour {
+        r: curve(c.r),
+        g: curve(c.g),
+        b: curve(c.b),
+    };
+    // The curve's output is display encoded, so we decode it back to linear
+    // to leave encoding to the transfer function.
+    let c = transform(OUTSET, c).clamp();
+    Colour::new(c.r.powf(2.2), c.g.powf(2.2), c.b.powf(2.2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_names_and_objects() {
+        let read = |json| serde_json::from_str::<ToneMap>(json);
+        assert!(matches!(read(r#""aces""#), Ok(ToneMap::Aces)));
+        assert!(matches!(
+            read(r#""extended_reinhard""#),
+            Ok(ToneMap::ExtendedReinhard { white }) if white == ToneMap::default_white()
+        ));
+        assert!(matches!(
+            read(r#"{"type": "extended_reinhard", "white": 8.0}"#),
+            Ok(ToneMap::ExtendedReinhard { white }) if white == 8.0
+        ));
+        assert!(read(r#""acse""#).is_err());
+
+        let read = |json| serde_json::from_str::<Transfer>(json);
+        assert!(matches!(read(r#""srgb""#), Ok(Transfer::Srgb)));
+        assert!(matches!(
+            read(r#"{"type": "gamma", "gamma": 1.8}"#),
+            Ok(Transfer::Gamma { gamma }) if gamma == 1.8
+        ));
+    }
+
+    #[test]
+    fn round_trip() {
+        let json = serde_json::to_string(&Transfer::Gamma { gamma: 2.4 }).unwrap();
+        assert_eq!(json, r#"{"type":"gamma","gamma":2.4}"#);
+        assert!(matches!(
+            serde_json::from_str(&json),
+            Ok(Transfer::Gamma { gamma }) if gamma == 2.4
+        ));
+    }
+}
@@ -69,6 +69,19 @@ impl V3 {
         }
     }
 
+    /// Two unit vectors at right angles to this one, which should be a unit
+    /// vector, and to each other.
+    pub fn orthonormal_basis(self) -> (V3, V3) {
+        let other = if self.x.abs() > 0.9 {
+            V3::new(0.0, 1.0, 0.0)
+        } else {
+            V3::new(1.0, 0.0, 0.0)
+        };
+        let s = self.cross(other).normalize();
+        let t = self.cross(s);
+        (s, t)
+    }
+
     /// Find the magnitude of a vector.
     pub fn magnitude(self) -> f64 {
         // This holds since u•u = ||u||^2